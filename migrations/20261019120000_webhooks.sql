@@ -0,0 +1,10 @@
+CREATE TABLE webhooks (
+    id INTEGER PRIMARY KEY NOT NULL,
+    -- NULL for global webhooks, which fire for every user
+    user_id TEXT,
+    url TEXT NOT NULL,
+    kind TEXT NOT NULL DEFAULT 'generic',
+    template TEXT,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (user_id) REFERENCES users(username) ON DELETE CASCADE
+);
\ No newline at end of file
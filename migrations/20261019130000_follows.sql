@@ -0,0 +1,14 @@
+CREATE TABLE follows (
+    user_id TEXT NOT NULL,
+    streamer TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (user_id, streamer),
+    FOREIGN KEY (user_id) REFERENCES users(username) ON DELETE CASCADE,
+    FOREIGN KEY (streamer) REFERENCES users(username) ON DELETE CASCADE
+);
+CREATE TABLE notification_settings (
+    user_id TEXT PRIMARY KEY NOT NULL,
+    browser BOOLEAN NOT NULL DEFAULT 1,
+    webhook_url TEXT,
+    FOREIGN KEY (user_id) REFERENCES users(username) ON DELETE CASCADE
+);
\ No newline at end of file
@@ -0,0 +1,10 @@
+CREATE TABLE restream_targets (
+    id INTEGER PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    -- encrypted with the configured secret_key
+    stream_key TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (user_id) REFERENCES users(username) ON DELETE CASCADE
+);
\ No newline at end of file
@@ -0,0 +1,15 @@
+ALTER TABLE users ADD COLUMN private BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN auto_record BOOLEAN NOT NULL DEFAULT 0;
+CREATE TABLE recordings (
+    id INTEGER PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    title TEXT,
+    file_path TEXT NOT NULL,
+    -- in bytes, set once the recording has finished
+    size INTEGER,
+    -- in seconds, set once the recording has finished
+    duration INTEGER,
+    started_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    ended_at DATETIME,
+    FOREIGN KEY (user_id) REFERENCES users(username) ON DELETE CASCADE
+);
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE recordings ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT 0;
\ No newline at end of file
@@ -0,0 +1,14 @@
+CREATE TABLE stream_keys (
+    id INTEGER PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    stream_key TEXT NOT NULL UNIQUE,
+    label TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    last_used_at DATETIME,
+    expires_at DATETIME,
+    -- comma separated, NULL allows every protocol
+    protocols TEXT,
+    -- comma separated, NULL allows every source address
+    cidrs TEXT,
+    FOREIGN KEY (user_id) REFERENCES users(username) ON DELETE CASCADE
+);
\ No newline at end of file
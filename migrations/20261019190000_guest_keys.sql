@@ -0,0 +1,15 @@
+CREATE TABLE guest_keys (
+    id INTEGER PRIMARY KEY NOT NULL,
+    stream_key TEXT NOT NULL UNIQUE,
+    display_name TEXT NOT NULL,
+    -- the stream name the guest publishes under
+    path TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    expires_at DATETIME NOT NULL,
+    -- set when the guest starts streaming
+    used_at DATETIME,
+    -- set when the guest stops streaming, the key can't be used afterwards
+    closed_at DATETIME,
+    FOREIGN KEY (created_by) REFERENCES users(username) ON DELETE CASCADE
+);
\ No newline at end of file
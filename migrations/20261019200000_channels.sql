@@ -0,0 +1,11 @@
+CREATE TABLE channels (
+    id INTEGER PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    stream_key TEXT NOT NULL UNIQUE,
+    title TEXT,
+    private BOOLEAN NOT NULL DEFAULT 0,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (user_id, name COLLATE NOCASE),
+    FOREIGN KEY (user_id) REFERENCES users(username) ON DELETE CASCADE
+);
\ No newline at end of file
@@ -0,0 +1,25 @@
+ALTER TABLE users ADD COLUMN category TEXT;
+ALTER TABLE users ADD COLUMN tags TEXT;
+CREATE VIRTUAL TABLE stream_search USING fts5(
+    username,
+    display_name,
+    stream_title,
+    category,
+    tags,
+    content='users'
+);
+INSERT INTO stream_search(stream_search) VALUES('rebuild');
+CREATE TRIGGER stream_search_insert AFTER INSERT ON users BEGIN
+    INSERT INTO stream_search(rowid, username, display_name, stream_title, category, tags)
+    VALUES (new.rowid, new.username, new.display_name, new.stream_title, new.category, new.tags);
+END;
+CREATE TRIGGER stream_search_delete AFTER DELETE ON users BEGIN
+    INSERT INTO stream_search(stream_search, rowid, username, display_name, stream_title, category, tags)
+    VALUES ('delete', old.rowid, old.username, old.display_name, old.stream_title, old.category, old.tags);
+END;
+CREATE TRIGGER stream_search_update AFTER UPDATE ON users BEGIN
+    INSERT INTO stream_search(stream_search, rowid, username, display_name, stream_title, category, tags)
+    VALUES ('delete', old.rowid, old.username, old.display_name, old.stream_title, old.category, old.tags);
+    INSERT INTO stream_search(rowid, username, display_name, stream_title, category, tags)
+    VALUES (new.rowid, new.username, new.display_name, new.stream_title, new.category, new.tags);
+END;
\ No newline at end of file
@@ -0,0 +1,21 @@
+CREATE TABLE chat_messages (
+    id INTEGER PRIMARY KEY NOT NULL,
+    stream TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    display_name TEXT NOT NULL,
+    body TEXT NOT NULL,
+    deleted BOOLEAN NOT NULL DEFAULT 0,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (user_id) REFERENCES users(username) ON DELETE CASCADE
+);
+CREATE INDEX chat_messages_stream ON chat_messages (stream, id);
+CREATE TABLE chat_bans (
+    id INTEGER PRIMARY KEY NOT NULL,
+    stream TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    expires_at DATETIME,
+    created_by TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (stream, user_id),
+    FOREIGN KEY (user_id) REFERENCES users(username) ON DELETE CASCADE
+);
\ No newline at end of file
@@ -0,0 +1,19 @@
+CREATE TABLE stream_stats (
+    id INTEGER PRIMARY KEY NOT NULL,
+    stream TEXT NOT NULL,
+    user_id TEXT,
+    sampled_at DATETIME NOT NULL,
+    duration INTEGER NOT NULL,
+    downsampled BOOLEAN NOT NULL DEFAULT 0,
+    viewers INTEGER NOT NULL,
+    peak_viewers INTEGER NOT NULL,
+    webrtc INTEGER NOT NULL,
+    llhls INTEGER NOT NULL,
+    hls INTEGER NOT NULL,
+    srt INTEGER NOT NULL,
+    bitrate INTEGER NOT NULL,
+    fps REAL,
+    FOREIGN KEY (user_id) REFERENCES users(username) ON DELETE CASCADE
+);
+CREATE INDEX stream_stats_stream ON stream_stats (stream, sampled_at);
+CREATE INDEX stream_stats_user ON stream_stats (user_id);
\ No newline at end of file
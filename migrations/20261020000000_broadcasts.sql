@@ -0,0 +1,15 @@
+CREATE TABLE broadcasts (
+    id INTEGER PRIMARY KEY NOT NULL,
+    stream TEXT NOT NULL,
+    user_id TEXT,
+    channel TEXT,
+    title TEXT,
+    protocol TEXT,
+    address TEXT,
+    started_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    ended_at DATETIME,
+    peak_viewers INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY (user_id) REFERENCES users(username) ON DELETE CASCADE
+);
+CREATE INDEX broadcasts_user ON broadcasts (user_id, started_at);
+CREATE INDEX broadcasts_stream ON broadcasts (stream, ended_at);
\ No newline at end of file
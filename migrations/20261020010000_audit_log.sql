@@ -0,0 +1,11 @@
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY NOT NULL,
+    actor TEXT,
+    target TEXT,
+    action TEXT NOT NULL,
+    diff TEXT,
+    address TEXT,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now'))
+);
+CREATE INDEX audit_log_actor ON audit_log (actor);
+CREATE INDEX audit_log_target ON audit_log (target);
\ No newline at end of file
@@ -0,0 +1,3 @@
+ALTER TABLE users ADD COLUMN bio TEXT;
+ALTER TABLE users ADD COLUMN links TEXT;
+ALTER TABLE users ADD COLUMN avatar_url TEXT;
\ No newline at end of file
@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN avatar TEXT;
+ALTER TABLE chat_messages ADD COLUMN avatar TEXT;
\ No newline at end of file
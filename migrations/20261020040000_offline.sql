@@ -0,0 +1,2 @@
+ALTER TABLE users ADD COLUMN offline_message TEXT;
+ALTER TABLE users ADD COLUMN offline_image TEXT;
\ No newline at end of file
@@ -0,0 +1,14 @@
+CREATE TABLE schedules (
+    id INTEGER PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    description TEXT,
+    starts_at DATETIME NOT NULL,
+    ends_at DATETIME,
+    broadcast_id INTEGER,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (user_id) REFERENCES users(username) ON DELETE CASCADE,
+    FOREIGN KEY (broadcast_id) REFERENCES broadcasts(id) ON DELETE SET NULL
+);
+CREATE INDEX schedules_user ON schedules (user_id, starts_at);
+CREATE INDEX schedules_starts ON schedules (starts_at);
\ No newline at end of file
@@ -0,0 +1,9 @@
+ALTER TABLE users ADD COLUMN stream_password TEXT;
+CREATE TABLE playback_grants (
+    token TEXT PRIMARY KEY NOT NULL,
+    user_id TEXT NOT NULL,
+    expires_at DATETIME NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (user_id) REFERENCES users(username) ON DELETE CASCADE
+);
+CREATE INDEX playback_grants_user ON playback_grants (user_id);
\ No newline at end of file
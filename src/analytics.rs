@@ -0,0 +1,207 @@
+//! Sampling the statistics of live streams into a time series, to see who streams when and how many watch.
+//!
+//! Samples are taken every `stats_interval` seconds. Samples older than `stats_downsample_after` seconds
+//! are merged into one sample per hour, so the table doesn't grow too much.
+
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{Timelike, Utc};
+use tower_cookies::Cookies;
+
+use crate::{
+    errors::OMError,
+    objects::{AppState, GrantQuery, OMConfig, StatsQuery, StatsSample, StatsSummary, User},
+    ome,
+    playback::check_access,
+    registry::{resolve, LiveStream},
+    Db,
+};
+
+/// The time range returned if no range is requested.
+const DEFAULT_RANGE_HOURS: i64 = 24;
+
+/// Store the current statistics of a live stream.
+async fn sample(state: &AppState, live: &LiveStream) -> Result<(), OMError> {
+    let (stream, owner) = resolve(&live.name, true, &state.db).await?;
+    let config = &state.config;
+    let app = &live.app;
+
+    let stats = ome::stream_stats(config, app, &stream).await?;
+    let fps = ome::stream_info(config, app, &stream)
+        .await
+        .ok()
+        .and_then(|i| i.framerate());
+    let count = |protocol: &str| stats.connections.get(protocol).copied().unwrap_or(0) as i64;
+
+    let now = Utc::now().naive_utc();
+    let user_id = owner.map(|u| u.username);
+    let duration = config.stats_interval as i64;
+    let viewers = stats.total_connections as i64;
+    let (webrtc, llhls, hls, srt) = (count("webrtc"), count("llhls"), count("hls"), count("srt"));
+    let bitrate = stats.last_throughput_in as i64;
+    sqlx::query!(
+        "
+        INSERT INTO stream_stats
+        (stream, user_id, sampled_at, duration, viewers, peak_viewers, webrtc, llhls, hls, srt, bitrate, fps)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ",
+        stream,
+        user_id,
+        now,
+        duration,
+        viewers,
+        viewers,
+        webrtc,
+        llhls,
+        hls,
+        srt,
+        bitrate,
+        fps
+    )
+    .execute(&state.db)
+    .await?;
+    Ok(())
+}
+
+/// Merge the samples older than `stats_downsample_after` into one sample per stream and hour.
+///
+/// Viewer counts, bitrate and frame rate are averaged, the peak is kept and the durations are added up.
+pub async fn downsample(db: &Db, config: &OMConfig) -> Result<(), OMError> {
+    let cutoff =
+        Utc::now().naive_utc() - chrono::Duration::seconds(config.stats_downsample_after as i64);
+    // Only merge whole hours, so that every hour ends up in a single sample
+    let Some(cutoff) = cutoff
+        .with_minute(0)
+        .and_then(|c| c.with_second(0))
+        .and_then(|c| c.with_nanosecond(0))
+    else {
+        return Ok(());
+    };
+
+    let mut tx = db.begin().await?;
+    sqlx::query!(
+        "
+        INSERT INTO stream_stats
+        (stream, user_id, sampled_at, duration, downsampled, viewers, peak_viewers, webrtc, llhls, hls, srt, bitrate, fps)
+        SELECT stream, user_id, strftime('%Y-%m-%d %H:00:00', sampled_at), SUM(duration), 1,
+        CAST(ROUND(AVG(viewers)) AS INTEGER), MAX(peak_viewers),
+        CAST(ROUND(AVG(webrtc)) AS INTEGER), CAST(ROUND(AVG(llhls)) AS INTEGER),
+        CAST(ROUND(AVG(hls)) AS INTEGER), CAST(ROUND(AVG(srt)) AS INTEGER),
+        CAST(ROUND(AVG(bitrate)) AS INTEGER), AVG(fps)
+        FROM stream_stats
+        WHERE downsampled = 0 AND sampled_at < ?
+        GROUP BY stream, user_id, strftime('%Y-%m-%d %H', sampled_at)
+        ",
+        cutoff
+    )
+    .execute(&mut tx)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM stream_stats WHERE downsampled = 0 AND sampled_at < ?",
+        cutoff
+    )
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Sample all live streams forever, at the configured interval.
+pub async fn run(state: AppState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(state.config.stats_interval));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        for stream in state.registry.streams() {
+            let _ = sample(&state, &stream).await;
+        }
+        let _ = downsample(&state.db, &state.config).await;
+    }
+}
+
+/// Get the statistics of a stream over time, oldest first. Defaults to the last 24 hours.
+pub async fn stats(
+    State(state): State<AppState>,
+    Path(stream): Path<String>,
+    cookies: Cookies,
+    Query(query): Query<StatsQuery>,
+    Query(grant): Query<GrantQuery>,
+) -> Result<Json<Vec<StatsSample>>, OMError> {
+    let user = User::from_req(State(state.db.clone()), cookies).await.ok();
+    let (stream, owner) = resolve(&stream, user.is_some(), &state.db).await?;
+    check_access(
+        owner.as_ref(),
+        user.as_ref(),
+        grant.grant.as_deref(),
+        &state.db,
+    )
+    .await?;
+
+    let to = query.to.unwrap_or_else(|| Utc::now().naive_utc());
+    let from = query
+        .from
+        .unwrap_or_else(|| to - chrono::Duration::hours(DEFAULT_RANGE_HOURS));
+    let samples = sqlx::query_as!(
+        StatsSample,
+        "
+        SELECT sampled_at, duration, viewers, peak_viewers, webrtc, llhls, hls, srt, bitrate, fps
+        FROM stream_stats
+        WHERE stream = ? AND sampled_at >= ? AND sampled_at <= ?
+        ORDER BY sampled_at
+        ",
+        stream,
+        from,
+        to
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(samples))
+}
+
+/// Get how long a user streamed and their most viewers, over all their streams and channels.
+pub async fn summary(
+    State(state): State<AppState>,
+    Path(stream): Path<String>,
+    Query(query): Query<GrantQuery>,
+    cookies: Cookies,
+) -> Result<Json<StatsSummary>, OMError> {
+    let viewer = User::from_req(State(state.db.clone()), cookies).await.ok();
+    let user = resolve(&stream, viewer.is_some(), &state.db)
+        .await?
+        .1
+        .ok_or(OMError::NotFound(stream))?;
+    check_access(
+        Some(&user),
+        viewer.as_ref(),
+        query.grant.as_deref(),
+        &state.db,
+    )
+    .await?;
+
+    let summary = sqlx::query!(
+        r#"
+        SELECT COALESCE(SUM(duration), 0) as "seconds!: i64",
+        COALESCE(MAX(peak_viewers), 0) as "peak_viewers!: i64",
+        MIN(sampled_at) as "first_streamed: chrono::NaiveDateTime",
+        MAX(sampled_at) as "last_streamed: chrono::NaiveDateTime"
+        FROM stream_stats WHERE user_id = ?
+        "#,
+        user.username
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(Json(StatsSummary {
+        username: user.username,
+        hours_streamed: summary.seconds as f64 / 3600.0,
+        peak_viewers: summary.peak_viewers,
+        first_streamed: summary.first_streamed,
+        last_streamed: summary.last_streamed,
+    }))
+}
@@ -0,0 +1,137 @@
+//! An audit log of security-relevant actions, like logins, permission changes and deleted keys.
+//!
+//! Entries never contain secrets like passwords or stream keys.
+
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRef, FromRequestParts, Query, State},
+    http::request::Parts,
+    Json,
+};
+use serde_json::Value;
+use tower_cookies::Cookies;
+
+use crate::{
+    errors::OMError,
+    objects::{AuditAction, AuditEntry, AuditPage, AuditQuery, OMConfig, User},
+    Db,
+};
+
+/// How many entries are returned per page if not requested otherwise.
+const DEFAULT_PER_PAGE: i64 = 50;
+/// The most entries that can be requested per page.
+const MAX_PER_PAGE: i64 = 200;
+
+/// The IP address of the client that made the request.
+///
+/// Uses the last address of the `X-Forwarded-For` header if `trust_proxy` is set, the address of the connection otherwise.
+/// The last address is the one the trusted proxy appended, all others are controlled by the client.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    OMConfig: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let forwarded = OMConfig::from_ref(state)
+            .trust_proxy
+            .then(|| parts.headers.get("x-forwarded-for")?.to_str().ok())
+            .flatten()
+            .and_then(|h| h.rsplit(',').next()?.trim().parse().ok());
+        let connected = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|c| c.0.ip());
+        Ok(Self(forwarded.or(connected)))
+    }
+}
+
+/// Add an entry to the audit log.
+///
+/// `actor` is the user that performed the action and `target` the user it was performed on.
+/// Failing to write the log doesn't fail the action itself.
+pub async fn log(
+    db: &Db,
+    actor: Option<&str>,
+    target: Option<&str>,
+    action: AuditAction,
+    diff: Option<Value>,
+    address: Option<IpAddr>,
+) {
+    let diff = diff.map(|d| d.to_string());
+    let address = address.map(|a| a.to_string());
+    let _ = sqlx::query!(
+        "
+        INSERT INTO audit_log (actor, target, action, diff, address)
+        VALUES (?, ?, ?, ?, ?)
+        ",
+        actor,
+        target,
+        action,
+        diff,
+        address
+    )
+    .execute(db)
+    .await;
+}
+
+/// Query the audit log, newest first. Only for admins.
+///
+/// Can be filtered by actor, target, action and time range.
+pub async fn audit_log(
+    State(db): State<Db>,
+    cookies: Cookies,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<AuditPage>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    if !user.is_admin() {
+        return Err(OMError::NoPermission);
+    }
+
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_PER_PAGE)
+        .clamp(1, MAX_PER_PAGE);
+    let offset = (page - 1) * per_page;
+
+    let entries = sqlx::query_as!(
+        AuditEntry,
+        r#"
+        SELECT id, actor, target, action as "action: AuditAction", diff, address, created_at
+        FROM audit_log
+        WHERE (?1 IS NULL OR actor = ?1 COLLATE NOCASE)
+        AND (?2 IS NULL OR target = ?2 COLLATE NOCASE)
+        AND (?3 IS NULL OR action = ?3)
+        AND (?4 IS NULL OR created_at >= ?4)
+        AND (?5 IS NULL OR created_at <= ?5)
+        ORDER BY id DESC
+        LIMIT ?6 OFFSET ?7
+        "#,
+        query.actor,
+        query.target,
+        query.action,
+        query.from,
+        query.to,
+        per_page,
+        offset
+    )
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(AuditPage {
+        entries,
+        page,
+        per_page,
+    }))
+}
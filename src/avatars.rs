@@ -0,0 +1,235 @@
+//! Uploading and serving profile pictures.
+//!
+//! Uploaded images are decoded, cropped to a square, scaled to [`SIZE`] and stored as PNG in `avatars_dir`.
+//! They are served with an `ETag`, which is also part of their url, so they can be cached.
+//! The same is used for other uploaded images, like the offline images in [`crate::offline`].
+
+use std::{io::Cursor, path::PathBuf};
+
+use axum::{
+    body::Bytes,
+    extract::{Multipart, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use image::{
+    imageops::FilterType,
+    io::{Limits, Reader},
+    ImageFormat,
+};
+use sha2::{Digest, Sha256};
+use tokio::task::spawn_blocking;
+use tower_cookies::Cookies;
+
+use crate::{
+    errors::OMError,
+    objects::{OMConfig, User},
+    Db,
+};
+
+/// Width and height of the stored avatars in pixels.
+const SIZE: u32 = 256;
+/// The largest width and height of an uploaded image in pixels.
+const MAX_DIMENSION: u32 = 4096;
+/// How long browsers may cache an avatar without asking again.
+const MAX_AGE: u64 = 3600;
+
+impl User {
+    /// The url of the profile picture. Uploaded avatars take precedence over `avatar_url`.
+    #[must_use]
+    pub fn avatar(&self, config: &OMConfig) -> Option<String> {
+        match &self.avatar {
+            Some(etag) => image_url(config, &format!("users/{}/avatar", self.username), etag),
+            None => self.avatar_url.clone(),
+        }
+    }
+}
+
+/// The url of an uploaded image, with the `ETag` appended so that changed images aren't taken from the cache.
+pub(crate) fn image_url(config: &OMConfig, path: &str, etag: &str) -> Option<String> {
+    config
+        .base_url
+        .join(&format!("{path}?v={etag}"))
+        .ok()
+        .map(String::from)
+}
+
+/// Path of the uploaded avatar of a user.
+fn avatar_path(config: &OMConfig, username: &str) -> PathBuf {
+    config.avatars_dir.join(format!("{username}.png"))
+}
+
+/// Decode an image, crop it to the given size and encode it as PNG.
+///
+/// Only PNG, JPEG, GIF and WebP images of at most [`MAX_DIMENSION`] pixels in width and height are accepted.
+fn normalize(data: &[u8], width: u32, height: u32) -> Result<Vec<u8>, OMError> {
+    let format = image::guess_format(data).map_err(|_| OMError::InvalidImage)?;
+    if !matches!(
+        format,
+        ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP
+    ) {
+        return Err(OMError::InvalidImage);
+    }
+    // Small files can still decode to huge images, so their dimensions are limited before decoding
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    let mut reader = Reader::with_format(Cursor::new(data), format);
+    reader.limits(limits);
+    let image = reader.decode().map_err(|_| OMError::InvalidImage)?;
+
+    let mut png = Vec::new();
+    image
+        .resize_to_fill(width, height, FilterType::Lanczos3)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|_| OMError::InvalidImage)?;
+    Ok(png)
+}
+
+/// Read an uploaded image from the field `name` of a multipart form.
+pub(crate) async fn read_upload(
+    multipart: &mut Multipart,
+    name: &str,
+    config: &OMConfig,
+) -> Result<Bytes, OMError> {
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| OMError::InvalidImage)?
+    {
+        if field.name() == Some(name) {
+            let data = field.bytes().await.map_err(|_| OMError::ImageTooLarge)?;
+            if data.len() > config.avatar_max_bytes {
+                return Err(OMError::ImageTooLarge);
+            }
+            return Ok(data);
+        }
+    }
+    Err(OMError::InvalidImage)
+}
+
+/// Normalize an uploaded image to the given size and store it at `path`. Returns the `ETag` of the stored image.
+pub(crate) async fn store(
+    data: Bytes,
+    width: u32,
+    height: u32,
+    path: PathBuf,
+) -> Result<String, OMError> {
+    let png = spawn_blocking(move || normalize(&data, width, height)).await??;
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    tokio::fs::write(&path, &png).await?;
+    Ok(hex(&Sha256::digest(&png)[..8]))
+}
+
+/// Delete a stored image, if it exists.
+pub(crate) async fn remove(path: PathBuf) -> Result<(), OMError> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Serve a stored image. Answers with `304 Not Modified` if the client already has the current version.
+///
+/// Images of `private` users may only be kept by the browser, not by shared caches.
+pub(crate) async fn serve(
+    headers: &HeaderMap,
+    etag: &str,
+    path: PathBuf,
+    private: bool,
+) -> Result<Response, OMError> {
+    let etag = format!("\"{etag}\"");
+    let visibility = if private { "private" } else { "public" };
+    let cache = [
+        (header::ETAG, etag.clone()),
+        (
+            header::CACHE_CONTROL,
+            format!("{visibility}, max-age={MAX_AGE}"),
+        ),
+    ];
+    let unchanged = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| h.split(',').any(|t| t.trim() == etag));
+    if unchanged {
+        return Ok((StatusCode::NOT_MODIFIED, cache).into_response());
+    }
+
+    let data = tokio::fs::read(path).await?;
+    Ok((
+        cache,
+        [(header::CONTENT_TYPE, "image/png".to_string())],
+        data,
+    )
+        .into_response())
+}
+
+/// Upload a profile picture for the currently logged in user, as the `avatar` field of a multipart form.
+pub async fn upload_avatar(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    cookies: Cookies,
+    mut multipart: Multipart,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+
+    let data = read_upload(&mut multipart, "avatar", &config).await?;
+    let etag = store(data, SIZE, SIZE, avatar_path(&config, &user.username)).await?;
+    sqlx::query!(
+        "UPDATE users SET avatar = ? WHERE username = ?",
+        etag,
+        user.username
+    )
+    .execute(&db)
+    .await?;
+
+    Ok(())
+}
+
+/// Remove the uploaded profile picture of the currently logged in user.
+pub async fn delete_avatar(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    cookies: Cookies,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+
+    sqlx::query!(
+        "UPDATE users SET avatar = NULL WHERE username = ?",
+        user.username
+    )
+    .execute(&db)
+    .await?;
+    remove(avatar_path(&config, &user.username)).await
+}
+
+/// Serve the uploaded profile picture of a user. Private users' avatars are only served to logged in users.
+pub async fn avatar(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    cookies: Cookies,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> Result<Response, OMError> {
+    let logged_in = User::from_req(State(db.clone()), cookies).await.is_ok();
+    let user = User::from_name(&username, &db)
+        .await
+        .filter(|u| !u.private || logged_in)
+        .ok_or(OMError::NotFound(username))?;
+    let etag = user.avatar.ok_or(OMError::ItemNotFound("Avatar"))?;
+
+    serve(
+        &headers,
+        &etag,
+        avatar_path(&config, &user.username),
+        user.private,
+    )
+    .await
+}
+
+/// Encode bytes as lowercase hex.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
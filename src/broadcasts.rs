@@ -0,0 +1,156 @@
+//! A permanent log of all broadcasts, built from the admission requests of `OvenMediaEngine`.
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::Utc;
+use tower_cookies::Cookies;
+
+use crate::{
+    errors::OMError,
+    objects::{Admission, Broadcast, BroadcastPage, BroadcastQuery, User},
+    schedule, Db,
+};
+
+/// How many broadcasts are returned per page if not requested otherwise.
+const DEFAULT_PER_PAGE: i64 = 50;
+/// The most broadcasts that can be requested per page.
+const MAX_PER_PAGE: i64 = 200;
+
+/// Log the start of a broadcast. Broadcasts of the same stream that were never closed are ended first.
+///
+/// Main streams of users are linked to the stream they scheduled around this time, if any.
+/// Broadcasts that were found by polling have no admission request, so their protocol and address are unknown.
+pub async fn start(
+    db: &Db,
+    adm: Option<&Admission>,
+    stream: &str,
+    user_id: Option<&str>,
+    channel: Option<&str>,
+    title: Option<&str>,
+) -> Result<(), OMError> {
+    end(db, stream).await?;
+
+    let protocol = adm.and_then(Admission::protocol);
+    let address = adm.and_then(Admission::address).map(|a| a.to_string());
+    let now = Utc::now().naive_utc();
+    let id = sqlx::query!(
+        "
+        INSERT INTO broadcasts (stream, user_id, channel, title, protocol, address, started_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        ",
+        stream,
+        user_id,
+        channel,
+        title,
+        protocol,
+        address,
+        now
+    )
+    .execute(db)
+    .await?
+    .last_insert_rowid();
+    // Scheduled streams are planned for the main stream, not for channels
+    if let (Some(user_id), None) = (user_id, channel) {
+        schedule::link(db, user_id, id).await?;
+    }
+    Ok(())
+}
+
+/// Log the end of the current broadcast of a stream, together with the most viewers it had.
+pub async fn end(db: &Db, stream: &str) -> Result<(), OMError> {
+    let now = Utc::now().naive_utc();
+    sqlx::query!(
+        "
+        UPDATE broadcasts SET ended_at = ?1, peak_viewers = COALESCE((
+            SELECT MAX(stream_stats.peak_viewers) FROM stream_stats
+            WHERE stream_stats.stream = broadcasts.stream
+            AND stream_stats.sampled_at >= broadcasts.started_at
+        ), 0)
+        WHERE stream = ?2 AND ended_at IS NULL
+        ",
+        now,
+        stream
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Get a page of broadcasts, newest first, optionally only those of one user.
+async fn page(
+    db: &Db,
+    username: Option<&str>,
+    query: &BroadcastQuery,
+) -> Result<BroadcastPage, OMError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_PER_PAGE)
+        .clamp(1, MAX_PER_PAGE);
+    let offset = (page - 1) * per_page;
+
+    let broadcasts = sqlx::query_as!(
+        Broadcast,
+        "
+        SELECT * FROM broadcasts
+        WHERE ?1 IS NULL OR user_id = ?1 COLLATE NOCASE
+        ORDER BY started_at DESC, id DESC
+        LIMIT ?2 OFFSET ?3
+        ",
+        username,
+        per_page,
+        offset
+    )
+    .fetch_all(db)
+    .await?;
+    let total = sqlx::query!(
+        r#"
+        SELECT COUNT(*) as "total!: i64" FROM broadcasts
+        WHERE ?1 IS NULL OR user_id = ?1 COLLATE NOCASE
+        "#,
+        username
+    )
+    .fetch_one(db)
+    .await?
+    .total;
+
+    Ok(BroadcastPage {
+        broadcasts,
+        page,
+        per_page,
+        total,
+    })
+}
+
+/// List the broadcasts of a user. Users can only see their own broadcasts, admins can see everyone's.
+pub async fn user_broadcasts(
+    State(db): State<Db>,
+    cookies: Cookies,
+    Path(username): Path<String>,
+    Query(query): Query<BroadcastQuery>,
+) -> Result<Json<BroadcastPage>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    if !user.username.eq_ignore_ascii_case(&username) && !user.is_admin() {
+        return Err(OMError::NoPermission);
+    }
+
+    Ok(Json(page(&db, Some(&username), &query).await?))
+}
+
+/// List the broadcasts of all users and guests, for auditing who used the server. Only for admins.
+///
+/// Can be filtered by user with the `username` query parameter.
+pub async fn broadcasts(
+    State(db): State<Db>,
+    cookies: Cookies,
+    Query(query): Query<BroadcastQuery>,
+) -> Result<Json<BroadcastPage>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    if !user.is_admin() {
+        return Err(OMError::NoPermission);
+    }
+
+    Ok(Json(page(&db, query.username.as_deref(), &query).await?))
+}
@@ -0,0 +1,40 @@
+//! Named channels, which let a user stream multiple streams at the same time.
+
+use crate::{objects::Channel, Db};
+
+/// Find the channel a stream key belongs to.
+pub async fn lookup(stream_key: &str, db: &Db) -> Option<Channel> {
+    sqlx::query_as!(
+        Channel,
+        "SELECT * FROM channels WHERE stream_key = ?",
+        stream_key
+    )
+    .fetch_optional(db)
+    .await
+    .ok()?
+}
+
+/// Find the channel that streams under the given name in `OvenMediaEngine`.
+pub async fn by_stream_name(name: &str, db: &Db) -> Option<Channel> {
+    sqlx::query_as!(
+        Channel,
+        "SELECT * FROM channels WHERE user_id || '_' || name = ? COLLATE NOCASE",
+        name
+    )
+    .fetch_optional(db)
+    .await
+    .ok()?
+}
+
+/// Find a channel of a user by its name.
+pub async fn by_name(username: &str, name: &str, db: &Db) -> Option<Channel> {
+    sqlx::query_as!(
+        Channel,
+        "SELECT * FROM channels WHERE user_id = ? AND name = ? COLLATE NOCASE",
+        username,
+        name
+    )
+    .fetch_optional(db)
+    .await
+    .ok()?
+}
@@ -0,0 +1,466 @@
+//! A live chat for every stream, over WebSockets.
+//!
+//! Everyone can read the chat, writing requires a session. The latest messages of every room are kept in memory,
+//! all messages are also stored in the database, so the history survives restarts.
+//! The streamer and admins can delete messages and time out or ban users.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    response::Response,
+};
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tower_cookies::Cookies;
+
+use crate::{
+    errors::OMError,
+    objects::{AppState, ChatMessage, GrantQuery, User},
+    playback::check_access,
+    registry::resolve,
+    Db,
+};
+
+/// How many messages of a room are kept in memory and sent to new viewers.
+const HISTORY: usize = 50;
+/// How many events are buffered for slow viewers before they start missing some.
+const CAPACITY: usize = 64;
+/// The maximum length of a message in characters.
+const MAX_MESSAGE_LEN: usize = 500;
+/// How many messages a user can send within [`RATE_WINDOW`].
+const RATE_LIMIT: usize = 5;
+/// The window for the rate limit.
+const RATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// Something that happened in a chat room, sent to the viewers as JSON.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChatEvent {
+    /// The latest messages, sent once after connecting.
+    History {
+        /// The messages, oldest first.
+        messages: Vec<ChatMessage>,
+    },
+    /// A new message.
+    Message(ChatMessage),
+    /// A message was deleted by a moderator.
+    Deleted {
+        /// Id of the deleted message.
+        id: i64,
+    },
+    /// A user was timed out by a moderator.
+    TimedOut {
+        /// The user that was timed out.
+        username: String,
+        /// Time in UTC when the user can write again.
+        until: NaiveDateTime,
+    },
+    /// A user was banned by a moderator.
+    Banned {
+        /// The user that was banned.
+        username: String,
+    },
+    /// The ban or timeout of a user was lifted.
+    Unbanned {
+        /// The user that can write again.
+        username: String,
+    },
+    /// A command failed, only sent to the viewer that sent it.
+    Error {
+        /// What went wrong.
+        message: String,
+    },
+}
+
+/// A command sent by a viewer as JSON.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChatCommand {
+    /// Send a message.
+    Message {
+        /// Text of the message.
+        body: String,
+    },
+    /// Delete a message, only for moderators.
+    Delete {
+        /// Id of the message.
+        id: i64,
+    },
+    /// Keep a user from writing for some time, only for moderators.
+    Timeout {
+        /// The user to time out.
+        username: String,
+        /// Duration of the timeout.
+        seconds: u32,
+    },
+    /// Keep a user from writing until they are unbanned, only for moderators.
+    Ban {
+        /// The user to ban.
+        username: String,
+    },
+    /// Lift the ban or timeout of a user, only for moderators.
+    Unban {
+        /// The user to unban.
+        username: String,
+    },
+}
+
+#[derive(Debug)]
+struct Room {
+    sender: broadcast::Sender<ChatEvent>,
+    history: Option<VecDeque<ChatMessage>>,
+}
+
+#[derive(Debug, Default)]
+struct Hub {
+    rooms: HashMap<String, Room>,
+    sent: HashMap<String, VecDeque<Instant>>,
+}
+
+/// Shared handle to all chat rooms.
+#[derive(Debug, Clone, Default)]
+pub struct ChatHub(Arc<Mutex<Hub>>);
+
+impl ChatHub {
+    fn lock(&self) -> MutexGuard<'_, Hub> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Receive all events of a room from now on, and the history if it's in memory.
+    fn join(&self, stream: &str) -> (broadcast::Receiver<ChatEvent>, Option<Vec<ChatMessage>>) {
+        let mut hub = self.lock();
+        let room = hub.rooms.entry(stream.to_string()).or_insert_with(|| Room {
+            sender: broadcast::channel(CAPACITY).0,
+            history: None,
+        });
+        let history = room.history.as_ref().map(|h| h.iter().cloned().collect());
+        (room.sender.subscribe(), history)
+    }
+
+    /// Forget a room once nobody is connected anymore. Its history will be loaded from the database again.
+    fn leave(&self, stream: &str) {
+        let mut hub = self.lock();
+        if hub
+            .rooms
+            .get(stream)
+            .is_some_and(|r| r.sender.receiver_count() == 0)
+        {
+            hub.rooms.remove(stream);
+        }
+    }
+
+    /// Keep the history that was loaded from the database, unless messages were sent in the meantime.
+    fn seed(&self, stream: &str, messages: &[ChatMessage]) {
+        if let Some(room) = self.lock().rooms.get_mut(stream) {
+            room.history
+                .get_or_insert_with(|| messages.iter().cloned().collect());
+        }
+    }
+
+    /// Send an event to everyone in a room.
+    fn send(&self, stream: &str, event: ChatEvent) {
+        let mut hub = self.lock();
+        let Some(room) = hub.rooms.get_mut(stream) else {
+            return;
+        };
+        if let Some(history) = &mut room.history {
+            match &event {
+                ChatEvent::Message(message) => {
+                    history.push_back(message.clone());
+                    if history.len() > HISTORY {
+                        history.pop_front();
+                    }
+                }
+                ChatEvent::Deleted { id } => history.retain(|m| m.id != *id),
+                _ => (),
+            }
+        }
+        let _ = room.sender.send(event);
+    }
+
+    /// Check whether a user may send another message, counting it if so.
+    fn allow(&self, username: &str) -> bool {
+        let mut hub = self.lock();
+        let now = Instant::now();
+        let sent = hub.sent.entry(username.to_string()).or_default();
+        while sent
+            .front()
+            .is_some_and(|t| now.duration_since(*t) > RATE_WINDOW)
+        {
+            sent.pop_front();
+        }
+        if sent.len() >= RATE_LIMIT {
+            return false;
+        }
+        sent.push_back(now);
+        true
+    }
+}
+
+/// A viewer connected to a room.
+struct Participant {
+    stream: String,
+    /// The streamer, if the stream belongs to a user.
+    owner: Option<String>,
+    user: Option<User>,
+}
+
+impl Participant {
+    fn moderator(&self) -> Result<&User, OMError> {
+        let user = self.user.as_ref().ok_or(OMError::InvalidSession)?;
+        if user.is_admin() || self.owner.as_ref() == Some(&user.username) {
+            Ok(user)
+        } else {
+            Err(OMError::NoPermission)
+        }
+    }
+}
+
+/// Load the latest messages of a room from the database, oldest first.
+async fn history(stream: &str, db: &Db) -> Result<Vec<ChatMessage>, OMError> {
+    let limit = HISTORY as i64;
+    let mut messages = sqlx::query_as!(
+        ChatMessage,
+        "
+        SELECT id, stream, user_id, display_name, avatar, body, created_at FROM chat_messages
+        WHERE stream = ? AND deleted = 0
+        ORDER BY id DESC
+        LIMIT ?
+        ",
+        stream,
+        limit
+    )
+    .fetch_all(db)
+    .await?;
+    messages.reverse();
+    Ok(messages)
+}
+
+/// Check whether a user is banned or timed out in a room.
+async fn is_banned(stream: &str, username: &str, db: &Db) -> Result<bool, OMError> {
+    let now = Utc::now().naive_utc();
+    let ban = sqlx::query!(
+        "
+        SELECT id FROM chat_bans
+        WHERE stream = ? AND user_id = ? AND (expires_at IS NULL OR expires_at > ?)
+        ",
+        stream,
+        username,
+        now
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(ban.is_some())
+}
+
+/// Ban a user from a room, until `expires_at` or forever. Returns the username as it is stored.
+async fn ban(
+    viewer: &Participant,
+    username: &str,
+    expires_at: Option<NaiveDateTime>,
+    db: &Db,
+) -> Result<String, OMError> {
+    let moderator = viewer.moderator()?;
+    let user = User::from_name(username, db)
+        .await
+        .ok_or_else(|| OMError::NotFound(username.to_string()))?;
+    if viewer.owner.as_deref() == Some(user.username.as_str()) {
+        return Err(OMError::NoPermission);
+    }
+    sqlx::query!(
+        "
+        INSERT INTO chat_bans (stream, user_id, expires_at, created_by) VALUES (?, ?, ?, ?)
+        ON CONFLICT (stream, user_id) DO UPDATE
+        SET expires_at = excluded.expires_at, created_by = excluded.created_by
+        ",
+        viewer.stream,
+        user.username,
+        expires_at,
+        moderator.username
+    )
+    .execute(db)
+    .await?;
+    Ok(user.username)
+}
+
+/// Run a command of a viewer.
+async fn command(state: &AppState, viewer: &Participant, cmd: ChatCommand) -> Result<(), OMError> {
+    let db = &state.db;
+    let event = match cmd {
+        ChatCommand::Message { body } => {
+            let user = viewer.user.as_ref().ok_or(OMError::InvalidSession)?;
+            let body = body.trim();
+            if body.is_empty() || body.chars().count() > MAX_MESSAGE_LEN {
+                return Err(OMError::InvalidMessage);
+            }
+            if is_banned(&viewer.stream, &user.username, db).await? {
+                return Err(OMError::Banned);
+            }
+            if !state.chat.allow(&user.username) {
+                return Err(OMError::RateLimited);
+            }
+            let avatar = user.avatar(&state.config);
+            let message = sqlx::query_as!(
+                ChatMessage,
+                "
+                INSERT INTO chat_messages (stream, user_id, display_name, avatar, body)
+                VALUES (?, ?, ?, ?, ?)
+                RETURNING id, stream, user_id, display_name, avatar, body, created_at
+                ",
+                viewer.stream,
+                user.username,
+                user.display_name,
+                avatar,
+                body
+            )
+            .fetch_one(db)
+            .await?;
+            ChatEvent::Message(message)
+        }
+        ChatCommand::Delete { id } => {
+            viewer.moderator()?;
+            let deleted = sqlx::query!(
+                "UPDATE chat_messages SET deleted = 1 WHERE id = ? AND stream = ?",
+                id,
+                viewer.stream
+            )
+            .execute(db)
+            .await?
+            .rows_affected();
+            if deleted == 0 {
+                return Err(OMError::ItemNotFound("Message"));
+            }
+            ChatEvent::Deleted { id }
+        }
+        ChatCommand::Timeout { username, seconds } => {
+            let until = Utc::now().naive_utc() + chrono::Duration::seconds(seconds.into());
+            let username = ban(viewer, &username, Some(until), db).await?;
+            ChatEvent::TimedOut { username, until }
+        }
+        ChatCommand::Ban { username } => {
+            let username = ban(viewer, &username, None, db).await?;
+            ChatEvent::Banned { username }
+        }
+        ChatCommand::Unban { username } => {
+            viewer.moderator()?;
+            let user = User::from_name(&username, db)
+                .await
+                .ok_or(OMError::NotFound(username))?;
+            let unbanned = sqlx::query!(
+                "DELETE FROM chat_bans WHERE stream = ? AND user_id = ?",
+                viewer.stream,
+                user.username
+            )
+            .execute(db)
+            .await?
+            .rows_affected();
+            if unbanned == 0 {
+                return Err(OMError::ItemNotFound("Ban"));
+            }
+            ChatEvent::Unbanned {
+                username: user.username,
+            }
+        }
+    };
+    state.chat.send(&viewer.stream, event);
+    Ok(())
+}
+
+/// Send an event to a single viewer.
+async fn send(socket: &mut WebSocket, event: &ChatEvent) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(event).unwrap_or_default();
+    socket.send(Message::Text(text)).await
+}
+
+/// Relay the events of a room to a viewer and handle their commands, until either side disconnects.
+async fn session(mut socket: WebSocket, state: AppState, viewer: Participant) {
+    let (mut events, history) = state.chat.join(&viewer.stream);
+    let messages = match history {
+        Some(messages) => messages,
+        None => {
+            let messages = self::history(&viewer.stream, &state.db)
+                .await
+                .unwrap_or_default();
+            state.chat.seed(&viewer.stream, &messages);
+            messages
+        }
+    };
+
+    if send(&mut socket, &ChatEvent::History { messages })
+        .await
+        .is_ok()
+    {
+        loop {
+            tokio::select! {
+                msg = socket.recv() => {
+                    let text = match msg {
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                        Some(Ok(_)) => continue,
+                    };
+                    let res = match serde_json::from_str(&text) {
+                        Ok(cmd) => command(&state, &viewer, cmd).await,
+                        Err(_) => Err(OMError::InvalidMessage),
+                    };
+                    if let Err(e) = res {
+                        let event = ChatEvent::Error { message: e.to_string() };
+                        if send(&mut socket, &event).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        if send(&mut socket, &event).await.is_err() {
+                            break;
+                        }
+                    }
+                    // Lagging viewers simply miss the events that were dropped
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+    }
+
+    drop(events);
+    state.chat.leave(&viewer.stream);
+}
+
+/// Join the chat of a stream over a WebSocket.
+///
+/// Viewers without a session can only read. Private streams are only available to logged in users,
+/// and password protected streams only with a playback grant.
+pub async fn chat(
+    State(state): State<AppState>,
+    Path(stream): Path<String>,
+    Query(query): Query<GrantQuery>,
+    cookies: Cookies,
+    ws: WebSocketUpgrade,
+) -> Result<Response, OMError> {
+    let user = User::from_req(State(state.db.clone()), cookies).await.ok();
+    let (stream, owner) = resolve(&stream, user.is_some(), &state.db).await?;
+    check_access(
+        owner.as_ref(),
+        user.as_ref(),
+        query.grant.as_deref(),
+        &state.db,
+    )
+    .await?;
+
+    let viewer = Participant {
+        stream,
+        owner: owner.map(|u| u.username),
+        user,
+    };
+    Ok(ws.on_upgrade(move |socket| session(socket, state, viewer)))
+}
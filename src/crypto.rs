@@ -1,11 +1,19 @@
-//! Password hashing, verification and random number generation
+//! Password hashing, verification, encryption and random number generation
 
 use argon2::{
     password_hash::{PasswordHash, PasswordHasher, SaltString},
     Argon2, PasswordVerifier,
 };
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
 use rand::distributions::{Alphanumeric, DistString};
 use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+/// Length of the nonce that is prepended to encrypted data.
+const NONCE_LEN: usize = 12;
 
 /// Generate a random byte array of the given length with the OS's secure random number generator.
 pub fn random_data(size: usize) -> Vec<u8> {
@@ -36,3 +44,28 @@ pub fn gen_stream_key() -> String {
         Alphanumeric.sample_string(&mut OsRng, 30)
     )
 }
+
+/// Build the cipher from the configured secret.
+fn cipher(secret: &str) -> ChaCha20Poly1305 {
+    let key = Sha256::digest(secret.as_bytes());
+    ChaCha20Poly1305::new(Key::from_slice(&key))
+}
+
+/// Encrypt a string with ChaCha20-Poly1305, returning the base64 encoded nonce and ciphertext.
+pub fn encrypt(secret: &str, plaintext: &str) -> Result<String, chacha20poly1305::Error> {
+    let nonce = random_data(NONCE_LEN);
+    let mut data = cipher(secret).encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())?;
+    data.splice(0..0, nonce);
+    Ok(base64::encode(data))
+}
+
+/// Decrypt a string that was encrypted with [encrypt].
+pub fn decrypt(secret: &str, encrypted: &str) -> Result<String, chacha20poly1305::Error> {
+    let data = base64::decode(encrypted).map_err(|_| chacha20poly1305::Error)?;
+    if data.len() < NONCE_LEN {
+        return Err(chacha20poly1305::Error);
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    let plaintext = cipher(secret).decrypt(Nonce::from_slice(nonce), ciphertext)?;
+    String::from_utf8(plaintext).map_err(|_| chacha20poly1305::Error)
+}
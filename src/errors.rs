@@ -9,20 +9,68 @@ pub enum OMError {
     NotFound(String),
     #[error("Username is already taken.")]
     NameTaken,
+    #[error("Expiry must be in the future.")]
+    InvalidExpiry,
     #[error("You don't have permission to do that.")]
     NoPermission,
     #[error("Username contains invalid characters.")]
     InvalidUsername,
+    #[error("Channel name contains invalid characters.")]
+    InvalidChannelName,
+    #[error("Category must be at most 50 characters long.")]
+    InvalidCategory,
+    #[error("Invalid tag: {0}")]
+    InvalidTag(String),
+    #[error("Bio must be at most 500 characters long, and at most 5 links are allowed.")]
+    InvalidProfileField,
+    #[error("Offline message must be at most 500 characters long.")]
+    InvalidOfflineMessage,
+    #[error("Scheduled streams need a title of at most 100 characters, a description of at most 1000 characters and must end after they start.")]
+    InvalidSchedule,
+    #[error("Messages must not be empty or longer than 500 characters.")]
+    InvalidMessage,
+    #[error("You are banned from this chat.")]
+    Banned,
+    #[error("You are sending messages too fast.")]
+    RateLimited,
+    #[error("Avatars must be PNG, JPEG, GIF or WebP images.")]
+    InvalidImage,
+    #[error("The image is too large.")]
+    ImageTooLarge,
     #[error("Invalid password.")]
     InvalidPassword,
+    #[error("The stream is password protected.")]
+    PasswordProtected,
+    #[error("URL must use http or https.")]
+    InvalidUrl,
+    #[error("URL must point to a public address.")]
+    InternalUrl,
+    #[error("URL must use rtmp or rtmps.")]
+    InvalidRtmpUrl,
+    #[error("Stream profile `{0}` doesn't exist.")]
+    InvalidProfile(String),
+    #[error("{0} not found.")]
+    ItemNotFound(&'static str),
+    #[error("The stream is not live.")]
+    NotLive,
+    #[error("The stream is already being recorded.")]
+    AlreadyRecording,
     #[error(transparent)]
     ReqwestError(reqwest::Error),
     #[error(transparent)]
     SqlxError(#[from] sqlx::Error),
     #[error(transparent)]
     JoinError(#[from] tokio::task::JoinError),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
     #[error("{0}")]
     ArgonError(String),
+    #[error("No `secret_key` has been configured.")]
+    NoSecretKey,
+    #[error("Failed to encrypt or decrypt data.")]
+    CryptoError,
+    #[error("Failed to build a valid URL from the configuration.")]
+    InvalidConfigUrl,
 }
 
 impl From<reqwest::Error> for OMError {
@@ -40,18 +88,45 @@ impl From<argon2::password_hash::Error> for OMError {
     }
 }
 
+impl From<chacha20poly1305::Error> for OMError {
+    fn from(_: chacha20poly1305::Error) -> Self {
+        OMError::CryptoError
+    }
+}
+
 impl OMError {
     const fn status_code(&self) -> StatusCode {
         match self {
             Self::InvalidSession => StatusCode::UNAUTHORIZED,
-            Self::NotFound(_) => StatusCode::NOT_FOUND,
-            Self::NameTaken => StatusCode::CONFLICT,
-            Self::NoPermission | Self::InvalidPassword => StatusCode::FORBIDDEN,
-            Self::InvalidUsername => StatusCode::BAD_REQUEST,
+            Self::NotFound(_) | Self::ItemNotFound(_) => StatusCode::NOT_FOUND,
+            Self::NameTaken | Self::NotLive | Self::AlreadyRecording => StatusCode::CONFLICT,
+            Self::NoPermission | Self::InvalidPassword | Self::PasswordProtected | Self::Banned => {
+                StatusCode::FORBIDDEN
+            }
+            Self::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            Self::ImageTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::InvalidUsername
+            | Self::InvalidChannelName
+            | Self::InvalidCategory
+            | Self::InvalidTag(_)
+            | Self::InvalidProfileField
+            | Self::InvalidOfflineMessage
+            | Self::InvalidMessage
+            | Self::InvalidSchedule
+            | Self::InvalidImage
+            | Self::InvalidUrl
+            | Self::InternalUrl
+            | Self::InvalidRtmpUrl
+            | Self::InvalidProfile(_)
+            | Self::InvalidExpiry => StatusCode::BAD_REQUEST,
             Self::SqlxError(_)
             | Self::JoinError(_)
+            | Self::IoError(_)
             | Self::ArgonError(_)
-            | Self::ReqwestError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            | Self::ReqwestError(_)
+            | Self::NoSecretKey
+            | Self::CryptoError
+            | Self::InvalidConfigUrl => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
@@ -0,0 +1,164 @@
+//! Events about streams going live or offline, pushed to the frontend via Server-Sent Events.
+
+use std::convert::Infallible;
+
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::Stream;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+
+use tower_cookies::Cookies;
+
+use crate::{
+    objects::{Channel, User},
+    Db,
+};
+
+/// How many events are buffered for slow subscribers before they start missing some.
+const CAPACITY: usize = 64;
+
+/// Something that happened to a stream.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    /// A user went live.
+    StreamStarted {
+        /// Username of the streaming user, URL safe.
+        username: String,
+        /// Name the gets displayed in the UI.
+        display_name: String,
+        /// Optional stream title.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        /// The channel that went live, if it isn't the main stream of the user.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        channel: Option<String>,
+        /// Whether the stream is hidden from viewers that aren't logged in. Never sent to clients.
+        #[serde(skip)]
+        private: bool,
+    },
+    /// A user went offline.
+    StreamEnded {
+        /// Username of the user that stopped streaming.
+        username: String,
+        /// The channel that went offline, if it isn't the main stream of the user.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        channel: Option<String>,
+        /// Whether the stream is hidden from viewers that aren't logged in. Never sent to clients.
+        #[serde(skip)]
+        private: bool,
+    },
+    /// A user changed their stream title.
+    TitleChanged {
+        /// Username of the user.
+        username: String,
+        /// The new stream title.
+        title: Option<String>,
+        /// The channel whose title changed, if it isn't the main stream of the user.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        channel: Option<String>,
+        /// Whether the stream is hidden from viewers that aren't logged in. Never sent to clients.
+        #[serde(skip)]
+        private: bool,
+    },
+}
+
+impl StreamEvent {
+    /// Returns a [`StreamEvent::StreamStarted`] for the given user.
+    #[must_use]
+    pub fn started(user: &User) -> Self {
+        Self::StreamStarted {
+            username: user.username.clone(),
+            display_name: user.display_name.clone(),
+            title: user.stream_title.clone(),
+            channel: None,
+            private: user.private,
+        }
+    }
+    /// Returns a [`StreamEvent::StreamStarted`] for a channel of the given user.
+    #[must_use]
+    pub fn channel_started(user: &User, channel: &Channel) -> Self {
+        Self::StreamStarted {
+            username: user.username.clone(),
+            display_name: user.display_name.clone(),
+            title: channel.title.clone(),
+            channel: Some(channel.name.clone()),
+            private: user.private || channel.private,
+        }
+    }
+    /// Returns a [`StreamEvent::StreamEnded`] for the main stream of a user.
+    #[must_use]
+    pub const fn ended(username: String, private: bool) -> Self {
+        Self::StreamEnded {
+            username,
+            channel: None,
+            private,
+        }
+    }
+    /// Whether the event must only be sent to logged in viewers.
+    #[must_use]
+    pub const fn is_private(&self) -> bool {
+        match self {
+            Self::StreamStarted { private, .. }
+            | Self::StreamEnded { private, .. }
+            | Self::TitleChanged { private, .. } => *private,
+        }
+    }
+    /// The name of the event, as sent in the `event` field of the SSE message.
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::StreamStarted { .. } => "stream_started",
+            Self::StreamEnded { .. } => "stream_ended",
+            Self::TitleChanged { .. } => "title_changed",
+        }
+    }
+}
+
+/// Shared handle to broadcast [`StreamEvent`]s to every subscriber.
+#[derive(Debug, Clone)]
+pub struct EventBus(broadcast::Sender<StreamEvent>);
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self(broadcast::channel(CAPACITY).0)
+    }
+}
+
+impl EventBus {
+    /// Send an event to all current subscribers. Events without subscribers are dropped.
+    pub fn send(&self, event: StreamEvent) {
+        let _ = self.0.send(event);
+    }
+    /// Receive all events sent from now on.
+    #[must_use]
+    pub fn subscribe(&self) -> broadcast::Receiver<StreamEvent> {
+        self.0.subscribe()
+    }
+}
+
+/// Stream all [`StreamEvent`]s to the client as Server-Sent Events.
+///
+/// Events of private streams are only sent to logged in viewers.
+pub async fn events(
+    State(db): State<Db>,
+    State(events): State<EventBus>,
+    cookies: Cookies,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let logged_in = User::from_req(State(db), cookies).await.is_ok();
+    // Lagging clients simply miss the events that were dropped
+    let stream = BroadcastStream::new(events.subscribe()).filter_map(move |event| {
+        let event = event.ok().filter(|e| logged_in || !e.is_private())?;
+        Event::default()
+            .event(event.name())
+            .json_data(&event)
+            .ok()
+            .map(Ok)
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
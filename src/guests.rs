@@ -0,0 +1,63 @@
+//! Temporary stream keys for guests without an account.
+
+use crate::{objects::GuestKey, Db};
+
+/// Find a guest key.
+pub async fn lookup(stream_key: &str, db: &Db) -> Option<GuestKey> {
+    sqlx::query_as!(
+        GuestKey,
+        "SELECT * FROM guest_keys WHERE stream_key = ?",
+        stream_key
+    )
+    .fetch_optional(db)
+    .await
+    .ok()?
+}
+
+/// Find the guest key that is currently streaming under the given name.
+pub async fn streaming(path: &str, db: &Db) -> Option<GuestKey> {
+    sqlx::query_as!(
+        GuestKey,
+        "
+        SELECT * FROM guest_keys
+        WHERE path = ? AND used_at IS NOT NULL AND closed_at IS NULL
+        ",
+        path
+    )
+    .fetch_optional(db)
+    .await
+    .ok()?
+}
+
+/// Check whether the name is already used by a guest key that hasn't been closed.
+pub async fn path_taken(path: &str, db: &Db) -> Result<bool, sqlx::Error> {
+    let taken = sqlx::query!(
+        "SELECT id FROM guest_keys WHERE path = ? COLLATE NOCASE AND closed_at IS NULL",
+        path
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(taken.is_some())
+}
+
+/// Mark a guest key as used.
+pub async fn open(key: &GuestKey, db: &Db) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE guest_keys SET used_at = datetime('now') WHERE id = ? AND used_at IS NULL",
+        key.id
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Mark a guest key as closed, so it can't be used again.
+pub async fn close(key: &GuestKey, db: &Db) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE guest_keys SET closed_at = datetime('now') WHERE id = ?",
+        key.id
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,344 @@
+//! Ingest and playback urls for the streams of a user.
+
+use url::Url;
+
+use crate::objects::{IngestInfo, OMConfig, PlaybackUrls, RtmpIngest, User};
+
+/// The host streamers publish to.
+fn host(config: &OMConfig) -> &str {
+    config
+        .ingest_host
+        .as_deref()
+        .or_else(|| config.ome_url.host_str())
+        .unwrap_or("localhost")
+}
+
+/// Parse a url that was assembled from the configuration.
+fn url(s: &str) -> Option<Url> {
+    Url::parse(s).ok()
+}
+
+/// Build all urls a user needs to publish and play their stream.
+#[must_use]
+pub fn ingest_info(config: &OMConfig, user: &User) -> Option<IngestInfo> {
+    let host = host(config);
+    let app = config.app_for(user);
+    let key = &user.stream_key;
+
+    let server = url(&format!("rtmp://{host}:{}/{app}", config.rtmp_port))?;
+    let rtmp = RtmpIngest {
+        url: server.join(&format!("{app}/{key}")).ok()?,
+        server,
+        stream_key: key.clone(),
+    };
+
+    let mut srt = url(&format!("srt://{host}:{}", config.srt_port))?;
+    srt.query_pairs_mut().append_pair(
+        "streamid",
+        &format!("srt://{host}:{}/{app}/{key}", config.srt_port),
+    );
+
+    let scheme = if config.whip_tls { "https" } else { "http" };
+    let whip = url(&format!(
+        "{scheme}://{host}:{}/{app}/{key}?direction=whip",
+        config.whip_port
+    ))?;
+
+    Some(IngestInfo {
+        rtmp,
+        srt,
+        whip,
+        playback: playback_urls(config, app, &user.username)?,
+    })
+}
+
+/// Build the urls to play a stream with, after it has been rewritten by the admission webhook.
+#[must_use]
+pub fn playback_urls(config: &OMConfig, app: &str, name: &str) -> Option<PlaybackUrls> {
+    let http = match &config.playback_url {
+        Some(url) => url.clone(),
+        None => {
+            let mut url = config.ws_url.clone();
+            let scheme = if url.scheme() == "wss" {
+                "https"
+            } else {
+                "http"
+            };
+            // Switching between these special schemes always works
+            url.set_scheme(scheme).ok()?;
+            url
+        }
+    };
+    let path = format!("{app}/{name}/");
+
+    Some(PlaybackUrls {
+        webrtc: config.ws_url.join(&path).ok()?,
+        llhls: http.join(&path).ok()?.join("llhls.m3u8").ok()?,
+        // Without the `./` the colon would make this an absolute url
+        hls: http.join(&path).ok()?.join("./ts:playlist.m3u8").ok()?,
+    })
+}
+
+/// Query parameters that may carry the stream key.
+const KEY_PARAMS: [&str; 2] = ["key", "stream_key"];
+
+/// A publish request, with the stream key separated from the url.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Publish {
+    /// The stream key that was supplied.
+    pub stream_key: String,
+    /// The url without the stream key, whose path ends with the application.
+    pub url: Url,
+}
+
+/// Remove the last path segment if there is more than just the application.
+fn drop_stream_name(url: &mut Url) {
+    let segments: Vec<String> = url
+        .path_segments()
+        .map(|s| s.map(String::from).collect())
+        .unwrap_or_default();
+    if segments.len() > 1 {
+        url.set_path(&segments[..segments.len() - 1].join("/"));
+    }
+}
+
+/// Keep all query parameters except the ones in `remove`.
+fn remove_params(url: &mut Url, remove: &[&str]) {
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| !remove.contains(&k.as_ref()))
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    if pairs.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+}
+
+/// The query parameter that carries a playback grant.
+const GRANT_PARAM: &str = "grant";
+
+/// A playback request, as sent to the admission webhook by `OvenMediaEngine`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Playback {
+    /// Name of the stream that is played.
+    pub stream: String,
+    /// The playback grant that was supplied, needed for password protected streams.
+    pub grant: Option<String>,
+}
+
+/// Find the stream name and playback grant in a playback request.
+///
+/// The stream name is the path segment after the application, or taken from the `streamid` of an SRT request.
+///
+/// ```
+/// # use ovenmitts::ingest::parse_playback;
+/// # use url::Url;
+/// let parse = |url: &str| {
+///     let playback = parse_playback(&Url::parse(url).unwrap()).unwrap();
+///     (playback.stream, playback.grant)
+/// };
+///
+/// // WebRTC
+/// assert_eq!(
+///     parse("wss://example.com:3333/stream/user?grant=token"),
+///     ("user".into(), Some("token".into()))
+/// );
+/// // LLHLS
+/// assert_eq!(
+///     parse("https://example.com:3333/stream/user/llhls.m3u8"),
+///     ("user".into(), None)
+/// );
+/// // SRT with the access control syntax as `streamid`
+/// assert_eq!(
+///     parse("srt://example.com:9999?streamid=%23!%3A%3Ar%3Dstream%2Fuser%3Fgrant%3Dtoken%2Cm%3Drequest"),
+///     ("user".into(), Some("token".into()))
+/// );
+/// ```
+#[must_use]
+pub fn parse_playback(url: &Url) -> Option<Playback> {
+    let streamid = url
+        .query_pairs()
+        .find(|(k, _)| k == "streamid")
+        .map(|(_, v)| v.into_owned());
+    if let Some(streamid) = streamid {
+        // Either `#!::r=app/stream,m=request` or an url like `srt://host:port/app/stream`
+        let target = match streamid.strip_prefix("#!::") {
+            Some(fields) => {
+                let resource = fields.split(',').find_map(|f| f.strip_prefix("r="))?;
+                url.join(&format!("/{}", resource.trim_start_matches('/')))
+            }
+            None => Url::parse(&streamid),
+        };
+        return parse_playback(&target.ok()?);
+    }
+
+    let stream = url
+        .path_segments()?
+        .filter(|s| !s.is_empty())
+        .nth(1)?
+        .to_string();
+    let grant = url
+        .query_pairs()
+        .find(|(k, _)| k == GRANT_PARAM)
+        .map(|(_, v)| v.into_owned());
+    Some(Playback { stream, grant })
+}
+
+/// Find the stream key in a publish request, for every ingest protocol `OvenMediaEngine` supports.
+///
+/// The key is taken from, in this order:
+/// - the bearer token of a WHIP request
+/// - the `streamid` of an SRT request
+/// - the `key` or `stream_key` query parameter
+/// - the last path segment, after the application
+#[must_use]
+pub fn parse_publish(url: &Url, authorization: Option<&str>) -> Option<Publish> {
+    let mut url = url.clone();
+
+    // The scheme is case-insensitive
+    let token = authorization
+        .and_then(|a| a.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"));
+    if let Some((_, token)) = token {
+        let token = token.trim();
+        if token.is_empty() {
+            return None;
+        }
+        drop_stream_name(&mut url);
+        return Some(Publish {
+            stream_key: token.to_string(),
+            url,
+        });
+    }
+
+    let streamid = url
+        .query_pairs()
+        .find(|(k, _)| k == "streamid")
+        .map(|(_, v)| v.into_owned());
+    if let Some(streamid) = streamid {
+        // Either `#!::r=app/key,m=publish` or an url like `srt://host:port/app/key`
+        let resource = match streamid.strip_prefix("#!::") {
+            Some(fields) => fields
+                .split(',')
+                .find_map(|f| f.strip_prefix("r="))?
+                .to_string(),
+            None => Url::parse(&streamid).map_or(streamid, |u| u.path().to_string()),
+        };
+        remove_params(&mut url, &["streamid"]);
+        url.set_path(&resource);
+        return parse_publish(&url, None);
+    }
+
+    let key = url
+        .query_pairs()
+        .find(|(k, _)| KEY_PARAMS.contains(&k.as_ref()))
+        .map(|(_, v)| v.into_owned());
+    if let Some(stream_key) = key {
+        remove_params(&mut url, &KEY_PARAMS);
+        return (!stream_key.is_empty()).then_some(Publish { stream_key, url });
+    }
+
+    let mut segments: Vec<String> = url.path_segments()?.map(String::from).collect();
+    // The first segment is the application
+    if segments.len() < 2 {
+        return None;
+    }
+    let stream_key = segments.pop().filter(|k| !k.is_empty())?;
+    url.set_path(&segments.join("/"));
+    Some(Publish { stream_key, url })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(url: &str, authorization: Option<&str>) -> Option<(String, String)> {
+        parse_publish(&Url::parse(url).unwrap(), authorization)
+            .map(|p| (p.stream_key, p.url.to_string()))
+    }
+
+    fn publish(stream_key: &str, url: &str) -> Option<(String, String)> {
+        Some((stream_key.into(), url.into()))
+    }
+
+    #[test]
+    fn key_in_path() {
+        assert_eq!(
+            parse("rtmp://example.com:1935/stream/secret", None),
+            publish("secret", "rtmp://example.com:1935/stream")
+        );
+        assert_eq!(parse("rtmp://example.com:1935/stream/", None), None);
+    }
+
+    #[test]
+    fn key_in_query() {
+        for param in KEY_PARAMS {
+            assert_eq!(
+                parse(
+                    &format!("rtmp://example.com:1935/stream?{param}=secret&other=1"),
+                    None
+                ),
+                publish("secret", "rtmp://example.com:1935/stream?other=1")
+            );
+        }
+        assert_eq!(
+            parse("rtmp://example.com:1935/stream?stream_key=", None),
+            None
+        );
+    }
+
+    #[test]
+    fn no_key() {
+        assert_eq!(parse("rtmp://example.com:1935/stream", None), None);
+        assert_eq!(parse("rtmp://example.com:1935/", None), None);
+        assert_eq!(parse("srt://example.com:9999", None), None);
+    }
+
+    #[test]
+    fn srt_streamid() {
+        assert_eq!(
+            parse(
+                "srt://example.com:9999?streamid=srt%3A%2F%2Fexample.com%3A9999%2Fstream%2Fsecret",
+                None
+            ),
+            publish("secret", "srt://example.com:9999/stream")
+        );
+        assert_eq!(
+            parse(
+                "srt://example.com:9999?streamid=%23!%3A%3Ar%3Dstream%2Fsecret%2Cm%3Dpublish",
+                None
+            ),
+            publish("secret", "srt://example.com:9999/stream")
+        );
+        // The access control syntax without a resource
+        assert_eq!(
+            parse(
+                "srt://example.com:9999?streamid=%23!%3A%3Am%3Dpublish",
+                None
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn whip_bearer_token() {
+        let url = "http://example.com:3333/stream/obs?direction=whip";
+        assert_eq!(
+            parse(url, Some("Bearer secret ")),
+            publish("secret", "http://example.com:3333/stream?direction=whip")
+        );
+        assert_eq!(
+            parse(url, Some("bearer secret")),
+            publish("secret", "http://example.com:3333/stream?direction=whip")
+        );
+        assert_eq!(parse(url, Some("Bearer ")), None);
+        assert_eq!(parse(url, Some("Bearer   ")), None);
+        // Other schemes fall back to the path
+        assert_eq!(
+            parse(url, Some("Basic secret")),
+            publish("obs", "http://example.com:3333/stream?direction=whip")
+        );
+    }
+}
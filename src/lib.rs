@@ -6,11 +6,33 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use sqlx::{Pool, Sqlite};
 
+pub mod analytics;
+pub mod audit;
+pub mod avatars;
+pub mod broadcasts;
+pub mod channels;
+pub mod chat;
 mod crypto;
 mod errors;
+pub mod events;
+pub mod guests;
+pub mod ingest;
+pub mod notify;
 pub mod objects;
+pub mod offline;
+mod ome;
+pub mod playback;
+pub mod presence;
+mod recording;
+pub mod registry;
+mod restream;
+pub mod retention;
 pub mod routes;
+pub mod schedule;
+pub mod search;
 pub mod static_files;
+pub mod stream_keys;
+pub mod thumbnail;
 
 /// The database connection pool.
 pub type Db = Pool<Sqlite>;
@@ -22,4 +44,16 @@ lazy_static! {
     /// - be between 4 and 25 characters long
     /// - only contain alphanumeric characters and underscores
     pub static ref USERNAME_RE: Regex = Regex::new("^[a-zA-Z0-9_]{4,25}$").unwrap();
+    /// A regex that matches a valid channel name.
+    ///
+    /// A valid channel name must:
+    /// - be between 1 and 20 characters long
+    /// - only contain alphanumeric characters
+    pub static ref CHANNEL_RE: Regex = Regex::new("^[a-zA-Z0-9]{1,20}$").unwrap();
+    /// A regex that matches a valid, lowercased tag.
+    ///
+    /// A valid tag must:
+    /// - be between 1 and 25 characters long
+    /// - only contain lowercase alphanumeric characters and dashes
+    pub static ref TAG_RE: Regex = Regex::new("^[a-z0-9-]{1,25}$").unwrap();
 }
@@ -1,5 +1,8 @@
+use std::net::SocketAddr;
+
 use axum::{
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    routing::{delete, get, post},
     Router,
 };
 use figment::{
@@ -10,11 +13,38 @@ use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
 use tower_cookies::CookieManagerLayer;
 
 use ovenmitts::{
+    analytics,
+    audit::audit_log,
+    avatars::{avatar, delete_avatar, upload_avatar},
+    broadcasts::{broadcasts, user_broadcasts},
+    chat::{chat, ChatHub},
+    events::{events, EventBus},
     objects::{AppState, OMConfig},
-    routes::{admission, list_users, login, logout, register, streams, update_user, user},
+    offline::{delete_offline_image, offline_image, upload_offline_image},
+    playback::{grant, PasswordAttempts},
+    presence::{heartbeat, viewers, ViewerPresence},
+    registry::{poll, StreamRegistry},
+    retention,
+    routes::{
+        admission, create_channel, create_guest_key, create_restream, create_stream_key,
+        create_webhook, delete_channel, delete_guest_key, delete_restream, delete_stream_key,
+        delete_webhook, follow, ingest, list_channels, list_follows, list_guest_keys,
+        list_restreams, list_stream_keys, list_users, list_webhooks, login, logout,
+        notification_settings, pin_recording, profile, profiles, recording_file, recordings,
+        register, search, start_recording, stop_recording, storage_usage, stream, streams,
+        unfollow, update_channel, update_notification_settings, update_user, user,
+    },
+    schedule::{
+        calendar, create_schedule, delete_schedule, list_schedule, upcoming, update_schedule,
+        user_calendar,
+    },
     static_files::{index, index_js, static_handler},
+    thumbnail::{thumbnail, ThumbnailCache},
 };
 
+/// Room for the multipart headers around an uploaded file.
+const FORM_OVERHEAD: usize = 16 * 1024;
+
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
     let path = std::env::var("MITTS_CONFIG").unwrap_or("mitts.toml".into());
@@ -31,6 +61,20 @@ async fn main() -> eyre::Result<()> {
 
     sqlx::migrate!().run(&pool).await?;
 
+    let state = AppState {
+        db: pool,
+        config: settings.clone(),
+        registry: StreamRegistry::default(),
+        events: EventBus::default(),
+        thumbnails: ThumbnailCache::default(),
+        chat: ChatHub::default(),
+        presence: ViewerPresence::default(),
+        password_attempts: PasswordAttempts::default(),
+    };
+    tokio::spawn(poll(state.clone()));
+    tokio::spawn(analytics::run(state.clone()));
+    tokio::spawn(retention::run(state.db.clone(), settings.clone()));
+
     let app = Router::new()
         .route("/admission", post(admission))
         .route("/user", get(user))
@@ -39,18 +83,81 @@ async fn main() -> eyre::Result<()> {
         .route("/user/register", post(register))
         .route("/user/list", get(list_users))
         .route("/user/update", post(update_user))
+        .route("/user/ingest", get(ingest))
+        .route("/user/keys", get(list_stream_keys).post(create_stream_key))
+        .route("/user/keys/:id", delete(delete_stream_key))
+        .route("/user/channels", get(list_channels).post(create_channel))
+        .route("/user/:username/broadcasts", get(user_broadcasts))
+        .route(
+            "/user/avatar",
+            post(upload_avatar)
+                .delete(delete_avatar)
+                .layer(DefaultBodyLimit::max(
+                    settings.avatar_max_bytes + FORM_OVERHEAD,
+                )),
+        )
+        .route("/users/:username", get(profile))
+        .route(
+            "/user/offline",
+            post(upload_offline_image)
+                .delete(delete_offline_image)
+                .layer(DefaultBodyLimit::max(
+                    settings.avatar_max_bytes + FORM_OVERHEAD,
+                )),
+        )
+        .route("/users/:username/avatar", get(avatar))
+        .route("/users/:username/offline", get(offline_image))
+        .route("/users/:username/schedule.ics", get(user_calendar))
+        .route("/user/schedule", get(list_schedule).post(create_schedule))
+        .route(
+            "/user/schedule/:id",
+            post(update_schedule).delete(delete_schedule),
+        )
+        .route("/schedule", get(upcoming))
+        .route("/schedule.ics", get(calendar))
+        .route("/broadcasts", get(broadcasts))
+        .route("/audit", get(audit_log))
+        .route(
+            "/user/channels/:name",
+            post(update_channel).delete(delete_channel),
+        )
+        .route("/user/webhooks", get(list_webhooks).post(create_webhook))
+        .route("/user/webhooks/:id", delete(delete_webhook))
+        .route("/user/restreams", get(list_restreams).post(create_restream))
+        .route("/user/restreams/:id", delete(delete_restream))
+        .route("/user/follows", get(list_follows))
+        .route("/user/follows/:username", post(follow).delete(unfollow))
+        .route(
+            "/user/notifications",
+            get(notification_settings).post(update_notification_settings),
+        )
+        .route("/user/recording/start", post(start_recording))
+        .route("/user/recording/stop", post(stop_recording))
+        .route("/profiles", get(profiles))
+        .route("/guests", get(list_guest_keys).post(create_guest_key))
+        .route("/guests/:id", delete(delete_guest_key))
         .route("/streams", get(streams))
+        .route("/streams/:username", get(stream))
+        .route("/search", get(search))
+        .route("/streams/:username/thumbnail", get(thumbnail))
+        .route("/streams/:username/viewers", get(viewers).post(heartbeat))
+        .route("/streams/:username/grant", post(grant))
+        .route("/streams/:username/stats", get(analytics::stats))
+        .route("/streams/:username/stats/summary", get(analytics::summary))
+        .route("/recordings", get(recordings))
+        .route("/recordings/usage", get(storage_usage))
+        .route("/recordings/:id/file", get(recording_file))
+        .route("/recordings/:id/pin", post(pin_recording))
+        .route("/events", get(events))
+        .route("/chat/:stream", get(chat))
         .route("/", get(index))
         .route("/index.js", get(index_js))
         .route("/assets/*path", get(static_handler))
-        .with_state(AppState {
-            db: pool,
-            config: settings.clone(),
-        })
+        .with_state(state)
         .layer(CookieManagerLayer::new());
 
     axum::Server::bind(&settings.address)
-        .serve(app.into_make_service())
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await?;
 
     Ok(())
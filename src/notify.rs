@@ -0,0 +1,393 @@
+//! Outgoing webhook notifications when a user goes live.
+
+use std::{net::IpAddr, time::Duration};
+
+use serde_json::{json, Value};
+use tokio::net::lookup_host;
+use url::{Host, Url};
+
+use crate::{
+    errors::OMError,
+    objects::{OMConfig, User, Webhook, WebhookKind},
+    Db,
+};
+
+/// How often a webhook is tried before giving up.
+const ATTEMPTS: u32 = 5;
+/// Delay before the first retry, doubled after every failed attempt.
+const BACKOFF: Duration = Duration::from_secs(1);
+/// The message used if a webhook doesn't have its own template.
+const DEFAULT_TEMPLATE: &str = "{display_name} is now live: {title}\n{url}";
+
+/// Whether an address can be reached from the internet, as opposed to loopback, private and link-local addresses.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast())
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(ip.into()),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// Check that a webhook url uses http or https and that its host only resolves to public addresses,
+/// so webhooks can't be used to reach services on the server or in its network.
+pub async fn check_target(url: &Url) -> Result<(), OMError> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(OMError::InvalidUrl);
+    };
+    let addresses: Vec<IpAddr> = match url.host() {
+        Some(Host::Ipv4(ip)) => vec![ip.into()],
+        Some(Host::Ipv6(ip)) => vec![ip.into()],
+        Some(Host::Domain(domain)) => lookup_host((domain, 0))
+            .await
+            .map_err(|_| OMError::InvalidUrl)?
+            .map(|a| a.ip())
+            .collect(),
+        None => return Err(OMError::InvalidUrl),
+    };
+    if addresses.is_empty() || !addresses.into_iter().all(is_public) {
+        return Err(OMError::InternalUrl);
+    }
+    Ok(())
+}
+
+/// Fill in the placeholders of a template.
+///
+/// Supported placeholders are `{username}`, `{display_name}`, `{title}` and `{url}`, which is the url of OvenMitts.
+#[must_use]
+pub fn render(template: &str, user: &User, url: &str) -> String {
+    template
+        .replace("{username}", &user.username)
+        .replace("{display_name}", &user.display_name)
+        .replace("{title}", user.stream_title.as_deref().unwrap_or_default())
+        .replace("{url}", url)
+}
+
+/// Build the JSON payload for a webhook of the given kind.
+#[must_use]
+pub fn payload(kind: WebhookKind, template: Option<&str>, user: &User, url: &str) -> Value {
+    let message = render(template.unwrap_or(DEFAULT_TEMPLATE), user, url);
+    match kind {
+        WebhookKind::Discord => json!({ "content": message }),
+        WebhookKind::Slack | WebhookKind::Matrix => json!({ "text": message }),
+        WebhookKind::Generic => json!({
+            "event": "stream_started",
+            "username": user.username,
+            "display_name": user.display_name,
+            "title": user.stream_title,
+            "url": url,
+            "message": message,
+        }),
+    }
+}
+
+/// Send a payload to a webhook, retrying with exponential backoff on network and server errors.
+///
+/// `backoff` is the delay before the first retry, which is doubled after every failed attempt.
+async fn deliver(url: &str, payload: &Value, backoff: Duration) -> Result<(), reqwest::Error> {
+    let client = reqwest::Client::new();
+    let mut delay = backoff;
+    let mut attempt = 1;
+
+    loop {
+        let res = client
+            .post(url)
+            .json(payload)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+
+        match res {
+            Ok(_) => return Ok(()),
+            Err(e) => {
+                // Client errors won't go away by retrying, except for rate limits
+                let retry = e
+                    .status()
+                    .is_none_or(|s| s.is_server_error() || s.as_u16() == 429);
+                if !retry || attempt >= ATTEMPTS {
+                    return Err(e);
+                }
+            }
+        }
+
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+/// Notify the user's own and all global webhooks, as well as the webhooks of their followers, that the user went live.
+/// Global webhooks are skipped for private users.
+///
+/// Failing webhooks are skipped, so that one broken target doesn't block the others.
+pub async fn stream_started(db: Db, config: OMConfig, user: User) {
+    let Ok(webhooks) = sqlx::query_as!(
+        Webhook,
+        r#"
+        SELECT id, user_id, url, kind as "kind: WebhookKind", template, created_at FROM webhooks
+        WHERE user_id = ? OR (user_id IS NULL AND NOT ?)
+        "#,
+        user.username,
+        user.private
+    )
+    .fetch_all(&db)
+    .await
+    else {
+        return;
+    };
+
+    let followers = sqlx::query!(
+        r#"
+        SELECT notification_settings.webhook_url as "webhook_url!" FROM follows
+        JOIN notification_settings
+        ON follows.user_id = notification_settings.user_id
+        WHERE follows.streamer = ? AND notification_settings.webhook_url IS NOT NULL
+        "#,
+        user.username
+    )
+    .fetch_all(&db)
+    .await
+    .unwrap_or_default();
+
+    let base_url = config.base_url.as_str();
+    let targets = webhooks
+        .into_iter()
+        .map(|w| {
+            let payload = payload(w.kind, w.template.as_deref(), &user, base_url);
+            (w.url, payload)
+        })
+        .chain(followers.into_iter().map(|f| {
+            let payload = payload(WebhookKind::Generic, None, &user, base_url);
+            (f.webhook_url, payload)
+        }));
+
+    let deliveries =
+        targets.map(|(url, payload)| async move { deliver(&url, &payload, BACKOFF).await });
+    futures::future::join_all(deliveries).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        time::Instant,
+    };
+
+    use super::*;
+
+    /// Backoff used in the tests, so that retries don't slow them down.
+    const TEST_BACKOFF: Duration = Duration::from_millis(1);
+
+    fn user() -> User {
+        User {
+            username: "alice".into(),
+            display_name: "Alice".into(),
+            password: String::new(),
+            stream_key: String::new(),
+            permissions: None,
+            stream_title: Some("Speedrun".into()),
+            private: false,
+            auto_record: false,
+            stream_profile: None,
+            category: None,
+            tags: None,
+            bio: None,
+            links: None,
+            avatar_url: None,
+            avatar: None,
+            offline_message: None,
+            offline_image: None,
+            stream_password: None,
+        }
+    }
+
+    /// Start a local HTTP server that answers the requests with the given status codes, in order.
+    /// The last status code is repeated once the list runs out.
+    ///
+    /// Returns its url and the bodies of all requests it received.
+    async fn stand_in(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<Value>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+
+        let received = bodies.clone();
+        tokio::spawn(async move {
+            for i in 0.. {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let body = read_body(&mut socket).await;
+                received
+                    .lock()
+                    .unwrap()
+                    .push(serde_json::from_slice(&body).unwrap());
+
+                let status = statuses[i.min(statuses.len() - 1)];
+                let response = format!(
+                    "HTTP/1.1 {status} Test\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        (url, bodies)
+    }
+
+    /// Read a request with a `Content-Length` header and return its body.
+    async fn read_body(socket: &mut tokio::net::TcpStream) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            data.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&data);
+            if let Some(end) = text.find("\r\n\r\n") {
+                let length: usize = text[..end]
+                    .lines()
+                    .find_map(|l| {
+                        let (name, value) = l.split_once(':')?;
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse().ok())?
+                    })
+                    .unwrap_or(0);
+                if data.len() >= end + 4 + length {
+                    return data[end + 4..end + 4 + length].to_vec();
+                }
+            }
+            assert!(n > 0, "connection closed before the request was complete");
+        }
+    }
+
+    #[test]
+    fn renders_placeholders() {
+        let message = render(
+            "{display_name} ({username}): {title} {url}",
+            &user(),
+            "https://example.com/",
+        );
+        assert_eq!(message, "Alice (alice): Speedrun https://example.com/");
+    }
+
+    #[test]
+    fn payload_per_kind() {
+        let url = "https://example.com/";
+        let message = "Alice is now live: Speedrun\nhttps://example.com/";
+        assert_eq!(
+            payload(WebhookKind::Discord, None, &user(), url),
+            json!({ "content": message })
+        );
+        assert_eq!(
+            payload(WebhookKind::Slack, None, &user(), url),
+            json!({ "text": message })
+        );
+        assert_eq!(
+            payload(
+                WebhookKind::Matrix,
+                Some("{username} is live"),
+                &user(),
+                url
+            ),
+            json!({ "text": "alice is live" })
+        );
+        assert_eq!(
+            payload(WebhookKind::Generic, None, &user(), url),
+            json!({
+                "event": "stream_started",
+                "username": "alice",
+                "display_name": "Alice",
+                "title": "Speedrun",
+                "url": url,
+                "message": message,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_internal_targets() {
+        for url in [
+            "http://127.0.0.1/hook",
+            "http://localhost:8080/hook",
+            "http://10.0.0.1/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://0.0.0.0/hook",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+        ] {
+            let url = Url::parse(url).unwrap();
+            assert!(
+                matches!(check_target(&url).await, Err(OMError::InternalUrl)),
+                "{url}"
+            );
+        }
+        let url = Url::parse("ftp://93.184.215.14/hook").unwrap();
+        assert!(matches!(check_target(&url).await, Err(OMError::InvalidUrl)));
+        let url = Url::parse("https://93.184.215.14/hook").unwrap();
+        assert!(check_target(&url).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn delivers_payload() {
+        let (url, bodies) = stand_in(vec![200]).await;
+        let payload = payload(WebhookKind::Discord, None, &user(), "https://example.com/");
+
+        deliver(&url, &payload, TEST_BACKOFF).await.unwrap();
+        assert_eq!(*bodies.lock().unwrap(), vec![payload]);
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let (url, bodies) = stand_in(vec![500, 503, 429, 200]).await;
+
+        deliver(&url, &json!({}), TEST_BACKOFF).await.unwrap();
+        assert_eq!(bodies.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        let (url, bodies) = stand_in(vec![404, 200]).await;
+
+        let err = deliver(&url, &json!({}), TEST_BACKOFF).await.unwrap_err();
+        assert_eq!(err.status().map(|s| s.as_u16()), Some(404));
+        assert_eq!(bodies.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_last_attempt() {
+        let (url, bodies) = stand_in(vec![500]).await;
+
+        let err = deliver(&url, &json!({}), TEST_BACKOFF).await.unwrap_err();
+        assert_eq!(err.status().map(|s| s.as_u16()), Some(500));
+        assert_eq!(bodies.lock().unwrap().len(), ATTEMPTS as usize);
+    }
+
+    #[tokio::test]
+    async fn retries_refused_connections() {
+        // Bind and drop a listener to find a port nobody listens on
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        drop(listener);
+
+        let start = Instant::now();
+        let err = deliver(&url, &json!({}), TEST_BACKOFF).await.unwrap_err();
+        assert!(err.is_connect());
+        // Every attempt but the last is followed by a doubled delay
+        let waited = TEST_BACKOFF * (2u32.pow(ATTEMPTS - 1) - 1);
+        assert!(start.elapsed() >= waited);
+    }
+}
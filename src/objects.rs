@@ -2,12 +2,20 @@
 
 use axum::extract::{FromRef, State};
 use chrono::NaiveDateTime;
-use serde::{Deserialize, Serialize};
-use std::{net::SocketAddr, path::PathBuf};
+use ipnet::IpNet;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+};
 use tower_cookies::Cookies;
 use url::Url;
 
-use crate::{errors::OMError, Db};
+use crate::{
+    chat::ChatHub, errors::OMError, events::EventBus, playback::PasswordAttempts,
+    presence::ViewerPresence, registry::StreamRegistry, thumbnail::ThumbnailCache, Db,
+};
 
 /// Session data for a user.
 #[derive(Debug, Deserialize)]
@@ -65,23 +73,63 @@ impl AdmissionResponse {
 
 /// The request that is sent by `OvenMediaEngine`.
 ///
-/// Most of the request gets discarded, since all that's really needed for `OvenMitts` is the `url` field, because the stream key is found there.
+/// Most of the request gets discarded, since all that's really needed for `OvenMitts` is the `url` field, because the stream key is usually found there,
+/// and the `status` field, which tells whether the stream is opening or closing. WHIP clients send the stream key as bearer token in the `headers` instead.
 /// ```json
 /// {
 ///   "request": {
+///     "direction": "incoming",
+///     "status": "opening",
 ///     "url": "rtmp://example.com/stream/secret_stream_key"
 ///   }
 /// }
 /// ```
 #[derive(Debug, Deserialize)]
 pub struct Admission {
+    client: Option<AdmissionClient>,
     request: AdmissionRequest,
 }
 
-/// Helper struct to retrive the nested url.
+/// Helper struct to retrive the nested fields.
 #[derive(Debug, Deserialize)]
 struct AdmissionRequest {
+    #[serde(default)]
+    direction: AdmissionDirection,
+    #[serde(default)]
+    status: AdmissionStatus,
     url: Url,
+    /// Headers of the original request, which carry the bearer token for WHIP.
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    protocol: Option<String>,
+}
+
+/// Helper struct to retrieve the address of the client.
+#[derive(Debug, Deserialize)]
+struct AdmissionClient {
+    address: Option<IpAddr>,
+}
+
+/// Whether a stream is being published or played.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdmissionDirection {
+    /// A stream is being published to `OvenMediaEngine`.
+    #[default]
+    Incoming,
+    /// A stream is being played from `OvenMediaEngine`.
+    Outgoing,
+}
+
+/// Whether a connection is being opened or closed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdmissionStatus {
+    /// The connection is being opened, the response decides whether it is allowed.
+    #[default]
+    Opening,
+    /// The connection has been closed, the response is ignored by `OvenMediaEngine`.
+    Closing,
 }
 
 impl Admission {
@@ -90,6 +138,35 @@ impl Admission {
     pub const fn borrow_url(&self) -> &Url {
         &self.request.url
     }
+    /// Returns whether a stream is being published or played.
+    #[must_use]
+    pub const fn direction(&self) -> AdmissionDirection {
+        self.request.direction
+    }
+    /// Returns whether the connection is being opened or closed.
+    #[must_use]
+    pub const fn status(&self) -> AdmissionStatus {
+        self.request.status
+    }
+    /// Returns the protocol of the connection, like `rtmp` or `webrtc`.
+    #[must_use]
+    pub fn protocol(&self) -> Option<&str> {
+        self.request.protocol.as_deref()
+    }
+    /// Returns the address of the client.
+    #[must_use]
+    pub fn address(&self) -> Option<IpAddr> {
+        self.client.as_ref().and_then(|c| c.address)
+    }
+    /// Returns the `Authorization` header of the original request, if it was forwarded.
+    #[must_use]
+    pub fn authorization(&self) -> Option<&str> {
+        self.request
+            .headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("authorization"))
+            .map(|(_, v)| v.as_str())
+    }
 }
 
 /// The representation of a user in the database.
@@ -107,13 +184,37 @@ pub struct User {
     pub permissions: Option<String>,
     /// Title of the stream.
     pub stream_title: Option<String>,
+    /// Whether the stream and recordings are hidden from viewers that aren't logged in.
+    pub private: bool,
+    /// Whether streams get recorded automatically.
+    pub auto_record: bool,
+    /// Name of the [`StreamProfile`] the streams are published with. If None, the default profile will be used.
+    pub stream_profile: Option<String>,
+    /// Category of the stream.
+    pub category: Option<String>,
+    /// Tags of the stream, separated by spaces.
+    pub tags: Option<String>,
+    /// A short text about the user.
+    pub bio: Option<String>,
+    /// Links to other profiles or websites, separated by spaces.
+    pub links: Option<String>,
+    /// Url of the profile picture.
+    pub avatar_url: Option<String>,
+    /// `ETag` of the uploaded profile picture, which takes precedence over `avatar_url`.
+    pub avatar: Option<String>,
+    /// Message shown to viewers while the user isn't live.
+    pub offline_message: Option<String>,
+    /// `ETag` of the uploaded image shown to viewers while the user isn't live.
+    pub offline_image: Option<String>,
+    /// Hash of the password viewers need to play the streams. If None, the streams aren't password protected.
+    pub stream_password: Option<String>,
 }
 
 impl User {
     /// Check whether the user has a specified permission
     #[must_use]
     pub fn has_permission(&self, permission: &str) -> bool {
-        self.permissions.as_ref().map_or(false, |permissions| {
+        self.permissions.as_ref().is_some_and(|permissions| {
             permissions.contains(permission) || permissions.contains("IS_ADMIN")
         })
     }
@@ -122,6 +223,14 @@ impl User {
     pub fn is_admin(&self) -> bool {
         self.has_permission("IS_AMDIN")
     }
+    /// The tags of the stream, which are stored separated by spaces.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.tags.as_deref().unwrap_or_default().split_whitespace()
+    }
+    /// The links of the profile, which are stored separated by spaces.
+    pub fn links(&self) -> impl Iterator<Item = &str> {
+        self.links.as_deref().unwrap_or_default().split_whitespace()
+    }
     /// Find a User from the database from the username.
     /// Case-insensitive.
     pub async fn from_name(username: &str, db: &Db) -> Option<Self> {
@@ -159,7 +268,6 @@ impl User {
         let session = om_cookie.value();
         User::from_session(session, &db)
             .await
-            .map(|u| u)
             .ok_or(OMError::InvalidSession)
     }
 }
@@ -179,16 +287,51 @@ pub struct SendableUser {
     /// The current permissions of the user.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub permissions: Option<String>,
+    /// Whether the stream is hidden from viewers that aren't logged in.
+    pub private: bool,
+    /// Whether streams get recorded automatically.
+    pub auto_record: bool,
+    /// The selected stream profile.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_profile: Option<String>,
+    /// Category of the stream.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Tags of the stream.
+    pub tags: Vec<String>,
+    /// A short text about the user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bio: Option<String>,
+    /// Links to other profiles or websites.
+    pub links: Vec<String>,
+    /// Url of the profile picture.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
+    /// Message shown to viewers while the user isn't live.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offline_message: Option<String>,
+    /// Whether viewers need a password to play the streams.
+    pub password_protected: bool,
 }
 
 impl From<User> for SendableUser {
     fn from(user: User) -> Self {
         Self {
+            tags: user.tags().map(String::from).collect(),
+            links: user.links().map(String::from).collect(),
             username: user.username,
             display_name: user.display_name,
             stream_key: user.stream_key,
             stream_title: user.stream_title,
             permissions: user.permissions,
+            private: user.private,
+            auto_record: user.auto_record,
+            stream_profile: user.stream_profile,
+            category: user.category,
+            bio: user.bio,
+            avatar_url: user.avatar_url,
+            offline_message: user.offline_message,
+            password_protected: user.stream_password.is_some(),
         }
     }
 }
@@ -221,6 +364,105 @@ pub struct OMConfig {
     pub base_url: Url,
     /// Websocket url for the player.
     pub ws_url: Url,
+    #[serde(default = "default_poll_interval", deserialize_with = "interval")]
+    /// Seconds between polling OvenMediaEngine for the live streams.
+    pub poll_interval: u64,
+    /// Secret used to encrypt stored credentials, like restream keys.
+    pub secret_key: Option<String>,
+    #[serde(default = "default_recordings_dir")]
+    /// Directory OvenMediaEngine writes the recordings to, must be readable by OvenMitts.
+    pub recordings_dir: PathBuf,
+    /// Recordings older than this many days get deleted.
+    pub retention_max_age: Option<u64>,
+    /// The oldest recordings get deleted once all recordings together take up more bytes than this.
+    pub retention_max_bytes: Option<u64>,
+    /// The oldest recordings of a user get deleted once they take up more bytes than this.
+    pub retention_user_quota: Option<u64>,
+    #[serde(default = "default_cleanup_interval", deserialize_with = "interval")]
+    /// Seconds between enforcing the retention policy.
+    pub cleanup_interval: u64,
+    /// The url base of OvenMediaEngine's thumbnail publisher, e.g. `https://example.com:3333/`.
+    pub thumbnail_url: Option<Url>,
+    #[serde(default = "default_profiles")]
+    /// The available stream profiles. The first one is used if a user hasn't selected any.
+    pub profiles: Vec<StreamProfile>,
+    /// Hostname streamers publish to. Defaults to the host of `ome_url`.
+    pub ingest_host: Option<String>,
+    #[serde(default = "default_rtmp_port")]
+    /// Port of OvenMediaEngine's RTMP provider.
+    pub rtmp_port: u16,
+    #[serde(default = "default_srt_port")]
+    /// Port of OvenMediaEngine's SRT provider.
+    pub srt_port: u16,
+    #[serde(default = "default_whip_port")]
+    /// Port of OvenMediaEngine's WebRTC provider, used for WHIP.
+    pub whip_port: u16,
+    #[serde(default)]
+    /// Whether WHIP ingest uses https.
+    pub whip_tls: bool,
+    /// The url base for LLHLS and HLS playback. Defaults to `ws_url` with http(s).
+    pub playback_url: Option<Url>,
+    #[serde(default = "default_stats_interval", deserialize_with = "interval")]
+    /// Seconds between sampling the statistics of live streams.
+    pub stats_interval: u64,
+    #[serde(default = "default_stats_downsample_after")]
+    /// Samples older than this many seconds are merged into one sample per hour.
+    pub stats_downsample_after: u64,
+    #[serde(default = "default_avatars_dir")]
+    /// Directory uploaded images, like profile pictures and offline images, are stored in.
+    pub avatars_dir: PathBuf,
+    #[serde(default = "default_avatar_max_bytes")]
+    /// The largest image that can be uploaded, in bytes.
+    pub avatar_max_bytes: usize,
+    #[serde(default)]
+    /// Whether to take the client address from the `X-Forwarded-For` header, when running behind a reverse proxy.
+    pub trust_proxy: bool,
+}
+
+impl OMConfig {
+    /// Find the profile with the given name, falling back to the default profile.
+    #[must_use]
+    pub fn profile(&self, name: Option<&str>) -> Option<&StreamProfile> {
+        name.and_then(|name| self.profiles.iter().find(|p| p.name == name))
+            .or_else(|| self.profiles.first())
+    }
+    /// The OvenMediaEngine application the streams of a user are published to.
+    #[must_use]
+    pub fn app_for(&self, user: &User) -> &str {
+        self.profile(user.stream_profile.as_deref())
+            .map_or(DEFAULT_APP, |p| p.app.as_str())
+    }
+    /// The OvenMediaEngine application of the default profile.
+    #[must_use]
+    pub fn default_app(&self) -> &str {
+        self.profile(None).map_or(DEFAULT_APP, |p| p.app.as_str())
+    }
+    /// All OvenMediaEngine applications that streams can be published to.
+    #[must_use]
+    pub fn apps(&self) -> Vec<&str> {
+        let mut apps: Vec<&str> = self.profiles.iter().map(|p| p.app.as_str()).collect();
+        apps.sort_unstable();
+        apps.dedup();
+        if apps.is_empty() {
+            apps.push(DEFAULT_APP);
+        }
+        apps
+    }
+}
+
+/// The OvenMediaEngine application streams are published to if no profiles are configured.
+const DEFAULT_APP: &str = "stream";
+
+/// An output configuration for streams, implemented by an application in OvenMediaEngine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamProfile {
+    /// Name of the profile, which users select.
+    pub name: String,
+    /// The OvenMediaEngine application that implements the profile, e.g. with ABR transcoding.
+    pub app: String,
+    /// Description that gets displayed in the UI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
 }
 
 fn default_address() -> SocketAddr {
@@ -234,11 +476,61 @@ fn default_database() -> PathBuf {
     PathBuf::from("mitts.sqlite")
 }
 
-/// List of all current streams from OvenMediaEngine.
-#[derive(Debug, Deserialize)]
-pub struct Streams {
-    /// The vec containing all the stream names.
-    pub response: Vec<String>,
+/// Deserialize an interval in seconds, which has to be at least one second.
+fn interval<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let seconds = u64::deserialize(deserializer)?;
+    if seconds == 0 {
+        return Err(de::Error::custom("intervals must be at least 1 second"));
+    }
+    Ok(seconds)
+}
+
+const fn default_poll_interval() -> u64 {
+    10
+}
+
+fn default_recordings_dir() -> PathBuf {
+    PathBuf::from("recordings")
+}
+
+const fn default_cleanup_interval() -> u64 {
+    3600
+}
+
+const fn default_rtmp_port() -> u16 {
+    1935
+}
+
+const fn default_srt_port() -> u16 {
+    9999
+}
+
+const fn default_whip_port() -> u16 {
+    3333
+}
+
+const fn default_stats_interval() -> u64 {
+    60
+}
+
+const fn default_stats_downsample_after() -> u64 {
+    86400
+}
+
+fn default_avatars_dir() -> PathBuf {
+    PathBuf::from("avatars")
+}
+
+const fn default_avatar_max_bytes() -> usize {
+    2 * 1024 * 1024
+}
+
+fn default_profiles() -> Vec<StreamProfile> {
+    vec![StreamProfile {
+        name: "default".into(),
+        app: DEFAULT_APP.into(),
+        description: None,
+    }]
 }
 
 #[derive(Debug, Serialize)]
@@ -251,6 +543,73 @@ pub struct StreamResp {
     /// Optional stream title.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
+    /// Url of the latest preview image of the stream.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<Url>,
+    /// OvenMediaEngine application the stream was admitted into. Only set while it is live.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    /// Urls for playing the stream. Only set while it is live.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub playback: Option<PlaybackUrls>,
+    /// Whether the stream is published with a guest key, instead of by a user.
+    pub guest: bool,
+    /// Url of the profile picture of the user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+    /// Category of the stream.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Tags of the stream.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Whether the main stream of the user is live, as opposed to only their channels.
+    pub live: bool,
+    /// Whether viewers need a playback grant, which they get with the stream password.
+    pub password_protected: bool,
+    /// The live channels of the user.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<ChannelResp>,
+    /// Message shown while the user isn't live. Only set for a single stream that is offline.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offline_message: Option<String>,
+    /// Url of the image shown while the user isn't live. Only set for a single stream that is offline.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offline_image: Option<String>,
+    /// Time the user was last live in UTC. Only set for a single stream that is offline.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_live: Option<NaiveDateTime>,
+}
+
+/// Query parameters for filtering the list of live streams.
+#[derive(Debug, Deserialize)]
+pub struct StreamQuery {
+    /// Only list streams in this category.
+    pub category: Option<String>,
+    /// Only list streams with this tag.
+    pub tag: Option<String>,
+}
+
+impl StreamQuery {
+    /// Check whether the streams of a user match the filters.
+    #[must_use]
+    pub fn matches(&self, user: &User) -> bool {
+        let category = self.category.as_ref().is_none_or(|c| {
+            user.category
+                .as_ref()
+                .is_some_and(|uc| uc.eq_ignore_ascii_case(c.trim()))
+        });
+        let tag = self
+            .tag
+            .as_ref()
+            .is_none_or(|t| user.tags().any(|ut| ut.eq_ignore_ascii_case(t.trim())));
+        category && tag
+    }
+    /// Whether any filter is set.
+    #[must_use]
+    pub const fn is_filtered(&self) -> bool {
+        self.category.is_some() || self.tag.is_some()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -260,6 +619,18 @@ pub struct AppState {
     pub db: Db,
     /// The configuration for the server.
     pub config: OMConfig,
+    /// The streams that are currently live.
+    pub registry: StreamRegistry,
+    /// Broadcasts stream events to the frontend.
+    pub events: EventBus,
+    /// The latest preview images of the live streams.
+    pub thumbnails: ThumbnailCache,
+    /// The chat rooms of the streams.
+    pub chat: ChatHub,
+    /// The viewers that are currently watching the streams.
+    pub presence: ViewerPresence,
+    /// The recent stream password attempts.
+    pub password_attempts: PasswordAttempts,
 }
 
 impl FromRef<AppState> for Db {
@@ -274,6 +645,42 @@ impl FromRef<AppState> for OMConfig {
     }
 }
 
+impl FromRef<AppState> for StreamRegistry {
+    fn from_ref(input: &AppState) -> Self {
+        input.registry.clone()
+    }
+}
+
+impl FromRef<AppState> for EventBus {
+    fn from_ref(input: &AppState) -> Self {
+        input.events.clone()
+    }
+}
+
+impl FromRef<AppState> for ThumbnailCache {
+    fn from_ref(input: &AppState) -> Self {
+        input.thumbnails.clone()
+    }
+}
+
+impl FromRef<AppState> for ChatHub {
+    fn from_ref(input: &AppState) -> Self {
+        input.chat.clone()
+    }
+}
+
+impl FromRef<AppState> for ViewerPresence {
+    fn from_ref(input: &AppState) -> Self {
+        input.presence.clone()
+    }
+}
+
+impl FromRef<AppState> for PasswordAttempts {
+    fn from_ref(input: &AppState) -> Self {
+        input.password_attempts.clone()
+    }
+}
+
 /// The struct used to update user attributes.
 #[derive(Debug, Deserialize)]
 pub struct UserUpdate {
@@ -289,4 +696,864 @@ pub struct UserUpdate {
     pub stream_title: Option<String>,
     /// The permissions, can only be set by admins.
     pub permissions: Option<String>,
+    /// Hide the stream from viewers that aren't logged in.
+    pub private: Option<bool>,
+    /// Record streams automatically.
+    pub auto_record: Option<bool>,
+    /// The new stream profile, must be one of the configured profiles.
+    pub stream_profile: Option<String>,
+    /// The new stream category. An empty category removes it.
+    pub category: Option<String>,
+    /// The new stream tags, replacing all previous ones.
+    pub tags: Option<Vec<String>>,
+    /// The new bio. An empty bio removes it.
+    pub bio: Option<String>,
+    /// The new links, replacing all previous ones.
+    pub links: Option<Vec<Url>>,
+    /// The new url of the profile picture. An empty url removes it.
+    pub avatar_url: Option<String>,
+    /// The new offline message. An empty message removes it.
+    pub offline_message: Option<String>,
+    /// The new password viewers need to play the streams. An empty password removes it.
+    pub stream_password: Option<String>,
+}
+
+/// The kind of service a webhook posts to, which decides the shape of the payload.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum WebhookKind {
+    /// Discord webhook, the message is sent as `content`.
+    Discord,
+    /// Slack incoming webhook, the message is sent as `text`.
+    Slack,
+    /// Matrix webhook bridge (e.g. hookshot), the message is sent as `text`.
+    Matrix,
+    /// Any other receiver, all the stream information is sent as JSON.
+    #[default]
+    Generic,
+}
+
+/// The representation of a webhook in the database.
+#[derive(Debug, Clone, Serialize)]
+pub struct Webhook {
+    /// Unique id of the webhook.
+    pub id: i64,
+    /// The user whose streams trigger the webhook. Global webhooks fire for every user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    /// The url that gets called.
+    pub url: String,
+    /// The kind of service behind the url.
+    pub kind: WebhookKind,
+    /// Optional message template, see [`crate::notify::render`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Time of creation in UTC.
+    pub created_at: NaiveDateTime,
+}
+
+/// Payload for creating a webhook.
+#[derive(Debug, Deserialize)]
+pub struct WebhookCreate {
+    /// The url that gets called, must use http or https and point to a public address.
+    pub url: Url,
+    /// The kind of service behind the url.
+    #[serde(default)]
+    pub kind: WebhookKind,
+    /// Optional message template.
+    pub template: Option<String>,
+    /// Whether the webhook fires for every public user, needs the `CAN_GLOBAL_WEBHOOKS` permission.
+    #[serde(default)]
+    pub global: bool,
+}
+
+/// A streamer the user follows, together with their live status.
+#[derive(Debug, Serialize)]
+pub struct Followed {
+    /// Username of the followed streamer, URL safe.
+    pub username: String,
+    /// Name the gets displayed in the UI.
+    pub display_name: String,
+    /// Optional stream title.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Whether the streamer is currently live.
+    pub live: bool,
+    /// Time the user started following in UTC.
+    pub followed_at: NaiveDateTime,
+}
+
+/// How a user wants to be notified when a followed streamer goes live.
+#[derive(Debug, Serialize)]
+pub struct NotificationSettings {
+    /// Show browser notifications, handled by the frontend through the event feed.
+    pub browser: bool,
+    /// Url that gets a generic webhook payload for every followed streamer going live.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook_url: Option<String>,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            browser: true,
+            webhook_url: None,
+        }
+    }
+}
+
+/// Payload for changing the notification settings.
+#[derive(Debug, Deserialize)]
+pub struct NotificationUpdate {
+    /// Show browser notifications.
+    pub browser: bool,
+    /// Url for webhook notifications, must use http or https and point to a public address. None disables them.
+    pub webhook_url: Option<Url>,
+}
+
+/// The representation of a restream target in the database.
+#[derive(Debug, Clone)]
+pub struct RestreamTarget {
+    /// Unique id of the target.
+    pub id: i64,
+    /// The user whose stream gets republished.
+    pub user_id: String,
+    /// Name of the target that gets displayed in the UI.
+    pub name: String,
+    /// RTMP url of the external server.
+    pub url: String,
+    /// Encrypted stream key for the external server.
+    pub stream_key: String,
+    /// Time of creation in UTC.
+    pub created_at: NaiveDateTime,
+}
+
+impl RestreamTarget {
+    /// The id of the push in OvenMediaEngine.
+    #[must_use]
+    pub fn push_id(&self) -> String {
+        format!("mitts_{}", self.id)
+    }
+}
+
+/// Like the [RestreamTarget] struct, but without the stream key. Intended to be sent to the frontend.
+#[derive(Debug, Serialize)]
+pub struct SendableRestreamTarget {
+    /// Unique id of the target.
+    pub id: i64,
+    /// Name of the target that gets displayed in the UI.
+    pub name: String,
+    /// RTMP url of the external server.
+    pub url: String,
+    /// State of the push in OvenMediaEngine, if the stream is currently republished.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+}
+
+/// Payload for creating a restream target.
+#[derive(Debug, Deserialize)]
+pub struct RestreamCreate {
+    /// Name of the target that gets displayed in the UI.
+    pub name: String,
+    /// RTMP url of the external server, must use rtmp or rtmps.
+    pub url: Url,
+    /// Stream key for the external server.
+    pub stream_key: String,
+}
+
+/// The representation of a recording in the database.
+#[derive(Debug, Clone, Serialize)]
+pub struct Recording {
+    /// Unique id of the recording.
+    pub id: i64,
+    /// The user whose stream was recorded.
+    pub user_id: String,
+    /// Title of the stream when the recording started.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Path of the recorded file.
+    #[serde(skip)]
+    pub file_path: String,
+    /// Size of the file in bytes, once the recording has finished.
+    pub size: Option<i64>,
+    /// Length of the recording in seconds, once the recording has finished.
+    pub duration: Option<i64>,
+    /// Time the recording started in UTC.
+    pub started_at: NaiveDateTime,
+    /// Time the recording ended in UTC.
+    pub ended_at: Option<NaiveDateTime>,
+    /// Pinned recordings are never deleted by the retention policy.
+    pub pinned: bool,
+}
+
+impl Recording {
+    /// The id of the recording in OvenMediaEngine.
+    #[must_use]
+    pub fn record_id(&self) -> String {
+        format!("mitts_rec_{}", self.id)
+    }
+}
+
+/// Query parameters for listing recordings.
+#[derive(Debug, Deserialize)]
+pub struct RecordingQuery {
+    /// Only list the recordings of this user.
+    pub username: Option<String>,
+}
+
+/// Payload for starting or stopping a recording.
+#[derive(Debug, Deserialize)]
+pub struct RecordingControl {
+    /// The user whose stream is recorded. If None, the currently logged in user will be used.
+    pub username: Option<String>,
+}
+
+/// Payload for pinning or unpinning a recording.
+#[derive(Debug, Deserialize)]
+pub struct RecordingPin {
+    /// Whether the recording is exempt from the retention policy.
+    pub pinned: bool,
+}
+
+/// How much storage the recordings of a user take up.
+#[derive(Debug, Serialize)]
+pub struct StorageUsage {
+    /// The user the recordings belong to.
+    pub username: String,
+    /// Number of recordings.
+    pub recordings: i64,
+    /// Size of all recordings in bytes.
+    pub bytes: i64,
+    /// Size of the pinned recordings in bytes.
+    pub pinned_bytes: i64,
+    /// The configured quota in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota: Option<u64>,
+}
+
+/// Urls for publishing a stream via RTMP.
+#[derive(Debug, Serialize)]
+pub struct RtmpIngest {
+    /// The complete url, including the stream key.
+    pub url: Url,
+    /// The server url, as entered into OBS.
+    pub server: Url,
+    /// The stream key, as entered into OBS.
+    pub stream_key: String,
+}
+
+/// Urls for playing a stream.
+#[derive(Debug, Serialize)]
+pub struct PlaybackUrls {
+    /// WebRTC playback url.
+    pub webrtc: Url,
+    /// Low-latency HLS playlist url.
+    pub llhls: Url,
+    /// HLS playlist url.
+    pub hls: Url,
+}
+
+/// Response with all urls a user needs to publish and play their stream.
+#[derive(Debug, Serialize)]
+pub struct IngestInfo {
+    /// RTMP ingest.
+    pub rtmp: RtmpIngest,
+    /// SRT ingest url, with the stream key in the `streamid`.
+    pub srt: Url,
+    /// WebRTC ingest url for WHIP.
+    pub whip: Url,
+    /// Urls to play the stream with.
+    pub playback: PlaybackUrls,
+}
+
+/// The representation of an additional stream key in the database.
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamKey {
+    /// Unique id of the key.
+    pub id: i64,
+    /// The user the key belongs to.
+    #[serde(skip)]
+    pub user_id: String,
+    /// The stream key itself.
+    pub stream_key: String,
+    /// Label that gets displayed in the UI, like the name of the device.
+    pub label: String,
+    /// Time of creation in UTC.
+    pub created_at: NaiveDateTime,
+    /// Time the key was last used to publish in UTC.
+    pub last_used_at: Option<NaiveDateTime>,
+    /// Time after which the key can't be used anymore in UTC.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<NaiveDateTime>,
+    /// Comma separated protocols the key may be used with. If None, all protocols are allowed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocols: Option<String>,
+    /// Comma separated networks the key may be used from. If None, all addresses are allowed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cidrs: Option<String>,
+}
+
+impl StreamKey {
+    /// Check whether the key may be used to publish with the given protocol from the given address.
+    #[must_use]
+    pub fn allows(
+        &self,
+        protocol: Option<&str>,
+        address: Option<IpAddr>,
+        now: NaiveDateTime,
+    ) -> bool {
+        if self.expires_at.is_some_and(|e| e <= now) {
+            return false;
+        }
+        // An empty list of protocols doesn't restrict the key
+        if let Some(protocols) = self.protocols.as_deref().filter(|p| !p.trim().is_empty()) {
+            let Some(protocol) = protocol else {
+                return false;
+            };
+            if !protocols
+                .split(',')
+                .any(|p| p.trim().eq_ignore_ascii_case(protocol))
+            {
+                return false;
+            }
+        }
+        if let Some(cidrs) = &self.cidrs {
+            let Some(address) = address else {
+                return false;
+            };
+            if !cidrs
+                .split(',')
+                .filter_map(|c| c.trim().parse::<IpNet>().ok())
+                .any(|net| net.contains(&address))
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Payload for creating an additional stream key.
+#[derive(Debug, Deserialize)]
+pub struct StreamKeyCreate {
+    /// Label that gets displayed in the UI.
+    pub label: String,
+    /// Time after which the key can't be used anymore in UTC.
+    pub expires_at: Option<NaiveDateTime>,
+    /// Protocols the key may be used with, like `rtmp`, `srt` or `webrtc`.
+    pub protocols: Option<Vec<String>>,
+    /// Networks the key may be used from, like `192.168.0.0/24`.
+    pub cidrs: Option<Vec<IpNet>>,
+}
+
+/// The representation of a guest key in the database.
+#[derive(Debug, Clone, Serialize)]
+pub struct GuestKey {
+    /// Unique id of the key.
+    pub id: i64,
+    /// The stream key itself.
+    pub stream_key: String,
+    /// Name of the guest that gets displayed in the UI.
+    pub display_name: String,
+    /// The stream name the guest publishes under.
+    pub path: String,
+    /// The user that created the key.
+    pub created_by: String,
+    /// Time of creation in UTC.
+    pub created_at: NaiveDateTime,
+    /// Time after which the key can't be used anymore in UTC.
+    pub expires_at: NaiveDateTime,
+    /// Time the guest started streaming in UTC.
+    pub used_at: Option<NaiveDateTime>,
+    /// Time the guest stopped streaming in UTC, after which the key can't be used anymore.
+    pub closed_at: Option<NaiveDateTime>,
+}
+
+impl GuestKey {
+    /// Check whether the key can still be used to start streaming.
+    #[must_use]
+    pub fn is_valid(&self, now: NaiveDateTime) -> bool {
+        self.closed_at.is_none() && self.expires_at > now
+    }
+}
+
+/// Payload for creating a guest key.
+#[derive(Debug, Deserialize)]
+pub struct GuestKeyCreate {
+    /// Name of the guest that gets displayed in the UI.
+    pub display_name: String,
+    /// The stream name the guest publishes under, must be a valid username that isn't taken.
+    pub path: String,
+    /// Time after which the key can't be used anymore in UTC.
+    pub expires_at: NaiveDateTime,
+}
+
+/// The representation of a channel in the database.
+#[derive(Debug, Clone, Serialize)]
+pub struct Channel {
+    /// Unique id of the channel.
+    pub id: i64,
+    /// The user the channel belongs to.
+    pub user_id: String,
+    /// Name of the channel, unique per user.
+    pub name: String,
+    /// Stream key of the channel.
+    pub stream_key: String,
+    /// Title of the stream.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Whether the stream is hidden from viewers that aren't logged in.
+    pub private: bool,
+    /// Time of creation in UTC.
+    pub created_at: NaiveDateTime,
+}
+
+impl Channel {
+    /// The name of the stream in `OvenMediaEngine`, which is `username_channel`.
+    #[must_use]
+    pub fn stream_name(&self) -> String {
+        format!("{}_{}", self.user_id, self.name)
+    }
+}
+
+/// Payload for creating a channel.
+#[derive(Debug, Deserialize)]
+pub struct ChannelCreate {
+    /// Name of the channel, only alphanumeric characters.
+    pub name: String,
+    /// Title of the stream.
+    pub title: Option<String>,
+    /// Hide the stream from viewers that aren't logged in.
+    #[serde(default)]
+    pub private: bool,
+}
+
+/// Payload for updating a channel.
+#[derive(Debug, Deserialize)]
+pub struct ChannelUpdate {
+    /// The new title of the stream.
+    pub title: Option<String>,
+    /// Hide the stream from viewers that aren't logged in.
+    pub private: Option<bool>,
+}
+
+/// Query parameters for the search.
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    /// The words to search for in usernames, display names, stream titles, categories and tags.
+    pub q: String,
+}
+
+/// A user matching a search.
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    /// Username of the user, URL safe.
+    pub username: String,
+    /// Name that gets displayed in the UI.
+    pub display_name: String,
+    /// Optional stream title.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Category of the stream.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Tags of the stream.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Whether the user is currently live.
+    pub live: bool,
+}
+
+/// A live channel of a user.
+#[derive(Debug, Serialize)]
+pub struct ChannelResp {
+    /// Name of the channel.
+    pub name: String,
+    /// Name of the stream in OvenMediaEngine, used for playback.
+    pub stream: String,
+    /// Urls for playing the channel.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub playback: Option<PlaybackUrls>,
+    /// Optional stream title.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// A message in the chat of a stream.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatMessage {
+    /// Unique id of the message.
+    pub id: i64,
+    /// Name of the stream the message was sent in.
+    pub stream: String,
+    /// The user that sent the message.
+    pub user_id: String,
+    /// Display name of the user at the time the message was sent.
+    pub display_name: String,
+    /// Url of the profile picture of the user at the time the message was sent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+    /// Text of the message.
+    pub body: String,
+    /// Time the message was sent in UTC.
+    pub created_at: NaiveDateTime,
+}
+
+/// A logged in viewer of a stream.
+#[derive(Debug, Clone, Serialize)]
+pub struct Viewer {
+    /// Username of the viewer.
+    pub username: String,
+    /// Name that gets displayed in the UI.
+    pub display_name: String,
+}
+
+/// Response for the viewers of a stream.
+#[derive(Debug, Serialize)]
+pub struct ViewerList {
+    /// The logged in viewers.
+    pub viewers: Vec<Viewer>,
+    /// Number of viewers that aren't logged in.
+    pub anonymous: usize,
+    /// Number of connections `OvenMediaEngine` reports for the stream, if it could be reached.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connections: Option<u64>,
+}
+
+/// Query parameters for the statistics of a stream.
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    /// Start of the time range in UTC. Defaults to 24 hours before `to`.
+    pub from: Option<NaiveDateTime>,
+    /// End of the time range in UTC. Defaults to now.
+    pub to: Option<NaiveDateTime>,
+}
+
+/// The statistics of a stream at one point in time.
+#[derive(Debug, Serialize)]
+pub struct StatsSample {
+    /// Time the sample was taken in UTC. For downsampled samples, the start of the hour.
+    pub sampled_at: NaiveDateTime,
+    /// Seconds of streaming the sample covers.
+    pub duration: i64,
+    /// Number of viewers over all protocols.
+    pub viewers: i64,
+    /// The most viewers during the sample.
+    pub peak_viewers: i64,
+    /// Number of WebRTC viewers.
+    pub webrtc: i64,
+    /// Number of LLHLS viewers.
+    pub llhls: i64,
+    /// Number of HLS viewers.
+    pub hls: i64,
+    /// Number of SRT viewers.
+    pub srt: i64,
+    /// The incoming bitrate in bits per second.
+    pub bitrate: i64,
+    /// Frames per second of the incoming video.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f64>,
+}
+
+/// How much a user streamed, over all their streams and channels.
+#[derive(Debug, Serialize)]
+pub struct StatsSummary {
+    /// Username of the user.
+    pub username: String,
+    /// Total hours streamed.
+    pub hours_streamed: f64,
+    /// The most viewers the user ever had.
+    pub peak_viewers: i64,
+    /// Time of the first sample in UTC.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_streamed: Option<NaiveDateTime>,
+    /// Time of the latest sample in UTC.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_streamed: Option<NaiveDateTime>,
+}
+
+/// A broadcast in the log of all streams.
+#[derive(Debug, Serialize)]
+pub struct Broadcast {
+    /// Unique id of the broadcast.
+    pub id: i64,
+    /// Name of the stream in OvenMediaEngine.
+    pub stream: String,
+    /// The user that streamed, None for guests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    /// The channel that was streamed to, if it wasn't the main stream of the user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    /// The stream title at the start of the broadcast.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The protocol the stream was published with, like `rtmp` or `srt`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    /// IP address the stream was published from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Time the broadcast started in UTC.
+    pub started_at: NaiveDateTime,
+    /// Time the broadcast ended in UTC, None while it is live.
+    pub ended_at: Option<NaiveDateTime>,
+    /// The most viewers during the broadcast.
+    pub peak_viewers: i64,
+}
+
+/// Query parameters for listing broadcasts.
+#[derive(Debug, Deserialize)]
+pub struct BroadcastQuery {
+    /// The page to return, starting at 1.
+    pub page: Option<i64>,
+    /// How many broadcasts are returned per page.
+    pub per_page: Option<i64>,
+    /// Only list the broadcasts of this user, only used for the list of all broadcasts.
+    pub username: Option<String>,
+}
+
+/// A page of the broadcast log.
+#[derive(Debug, Serialize)]
+pub struct BroadcastPage {
+    /// The broadcasts on this page, newest first.
+    pub broadcasts: Vec<Broadcast>,
+    /// The number of this page, starting at 1.
+    pub page: i64,
+    /// How many broadcasts are returned per page.
+    pub per_page: i64,
+    /// The number of broadcasts on all pages.
+    pub total: i64,
+}
+
+/// A security-relevant action in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// A user logged in.
+    Login,
+    /// Somebody tried to log in with a wrong username or password.
+    LoginFailed,
+    /// A user logged out.
+    Logout,
+    /// A new user registered.
+    Register,
+    /// A user changed their own password.
+    PasswordChanged,
+    /// An admin changed the password of a user.
+    PasswordReset,
+    /// An admin changed the permissions of a user.
+    PermissionsChanged,
+    /// An additional stream key was created.
+    StreamKeyCreated,
+    /// An additional stream key was deleted.
+    StreamKeyDeleted,
+    /// A channel with its own stream key was created.
+    ChannelCreated,
+    /// A channel and its stream key were deleted.
+    ChannelDeleted,
+    /// A guest key was created.
+    GuestKeyCreated,
+    /// A guest key was deleted.
+    GuestKeyDeleted,
+    /// A webhook was deleted.
+    WebhookDeleted,
+    /// A restream target was deleted.
+    RestreamDeleted,
+    /// OvenMediaEngine was told to reject an incoming stream.
+    AdmissionDenied,
+    /// The stream password was set, changed or removed.
+    StreamPasswordChanged,
+    /// A wrong stream password was posted for a playback grant.
+    StreamPasswordFailed,
+}
+
+/// An entry in the audit log.
+#[derive(Debug, Serialize)]
+pub struct AuditEntry {
+    /// Unique id of the entry.
+    pub id: i64,
+    /// The user that performed the action, None for anonymous requests and OvenMediaEngine.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    /// The user the action was performed on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// What happened.
+    pub action: AuditAction,
+    /// What changed, as a JSON object. Never contains secrets.
+    #[serde(
+        serialize_with = "serialize_json",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub diff: Option<String>,
+    /// IP address of the client.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// Time of the action in UTC.
+    pub created_at: NaiveDateTime,
+}
+
+/// Serialize JSON that is stored as text as the JSON itself.
+fn serialize_json<S: Serializer>(json: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    json.as_deref()
+        .and_then(|j| serde_json::from_str::<serde_json::Value>(j).ok())
+        .serialize(serializer)
+}
+
+/// Query parameters for the audit log.
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    /// Only list actions performed by this user.
+    pub actor: Option<String>,
+    /// Only list actions performed on this user.
+    pub target: Option<String>,
+    /// Only list actions of this kind.
+    pub action: Option<AuditAction>,
+    /// Only list actions at or after this time in UTC.
+    pub from: Option<NaiveDateTime>,
+    /// Only list actions at or before this time in UTC.
+    pub to: Option<NaiveDateTime>,
+    /// The page to return, starting at 1.
+    pub page: Option<i64>,
+    /// How many entries are returned per page.
+    pub per_page: Option<i64>,
+}
+
+/// A page of the audit log.
+#[derive(Debug, Serialize)]
+pub struct AuditPage {
+    /// The entries on this page, newest first.
+    pub entries: Vec<AuditEntry>,
+    /// The number of this page, starting at 1.
+    pub page: i64,
+    /// How many entries are returned per page.
+    pub per_page: i64,
+}
+
+/// The public profile of a user.
+///
+/// Built from a [`User`], but only with the fields that are safe to show to anyone.
+/// In particular, the stream key and the permissions are never part of it.
+#[derive(Debug, Serialize)]
+pub struct PublicProfile {
+    /// Username of the user, URL safe.
+    pub username: String,
+    /// Name that gets displayed in the UI.
+    pub display_name: String,
+    /// A short text about the user.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bio: Option<String>,
+    /// Url of the profile picture.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+    /// Links to other profiles or websites.
+    pub links: Vec<String>,
+    /// Optional stream title.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_title: Option<String>,
+    /// Category of the stream.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Tags of the stream.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Whether the user is currently live.
+    pub live: bool,
+    /// Time the last broadcast ended in UTC, or started if it is still live.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_streamed: Option<NaiveDateTime>,
+}
+
+impl PublicProfile {
+    /// Build the public profile of a user.
+    #[must_use]
+    pub fn new(
+        user: User,
+        config: &OMConfig,
+        live: bool,
+        last_streamed: Option<NaiveDateTime>,
+    ) -> Self {
+        Self {
+            avatar: user.avatar(config),
+            tags: user.tags().map(String::from).collect(),
+            links: user.links().map(String::from).collect(),
+            username: user.username,
+            display_name: user.display_name,
+            bio: user.bio,
+            stream_title: user.stream_title,
+            category: user.category,
+            live,
+            last_streamed,
+        }
+    }
+}
+
+/// A broadcast a user plans to stream.
+#[derive(Debug, Serialize)]
+pub struct ScheduledStream {
+    /// Unique id of the entry.
+    pub id: i64,
+    /// The user that plans to stream.
+    pub user_id: String,
+    /// Title of the planned stream.
+    pub title: String,
+    /// What the stream will be about.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Time the stream is planned to start in UTC.
+    pub starts_at: NaiveDateTime,
+    /// Time the stream is planned to end in UTC, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ends_at: Option<NaiveDateTime>,
+    /// The broadcast that took place, once the user went live near the planned start.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broadcast_id: Option<i64>,
+    /// Time of creation in UTC.
+    pub created_at: NaiveDateTime,
+}
+
+/// Payload for scheduling a stream, or changing a scheduled one.
+#[derive(Debug, Deserialize)]
+pub struct ScheduleCreate {
+    /// Title of the planned stream.
+    pub title: String,
+    /// What the stream will be about.
+    pub description: Option<String>,
+    /// Time the stream is planned to start in UTC.
+    pub starts_at: NaiveDateTime,
+    /// Time the stream is planned to end in UTC, if known.
+    pub ends_at: Option<NaiveDateTime>,
+}
+
+/// Query parameters for listing upcoming streams.
+#[derive(Debug, Deserialize)]
+pub struct ScheduleQuery {
+    /// Only list the streams of this user.
+    pub username: Option<String>,
+}
+
+/// Payload for getting a playback grant for a password protected stream.
+#[derive(Debug, Deserialize)]
+pub struct StreamPassword {
+    /// The stream password. Not needed by the owner of the stream and admins.
+    pub password: Option<String>,
+}
+
+/// A short-lived permission to play a password protected stream.
+#[derive(Debug, Serialize)]
+pub struct PlaybackGrant {
+    /// The token, which has to be added to the playback urls as `grant` query parameter.
+    pub grant: String,
+    /// Time the grant expires in UTC. Connections that were opened before keep playing.
+    pub expires_at: NaiveDateTime,
+}
+
+/// Query parameters for endpoints of a stream that need a playback grant if the stream is password protected.
+#[derive(Debug, Deserialize)]
+pub struct GrantQuery {
+    /// The token of a [`PlaybackGrant`].
+    pub grant: Option<String>,
 }
@@ -0,0 +1,104 @@
+//! What viewers see while a user isn't live: an offline message and an uploaded offline image.
+
+use std::path::PathBuf;
+
+use axum::{
+    extract::{Multipart, Path, State},
+    http::HeaderMap,
+    response::Response,
+};
+use tower_cookies::Cookies;
+
+use crate::{
+    avatars::{image_url, read_upload, remove, serve, store},
+    errors::OMError,
+    objects::{OMConfig, User},
+    Db,
+};
+
+/// Width of the stored offline images in pixels, cropped to 16:9 like the player.
+const WIDTH: u32 = 1280;
+/// Height of the stored offline images in pixels.
+const HEIGHT: u32 = 720;
+
+impl User {
+    /// The url of the uploaded offline image.
+    #[must_use]
+    pub fn offline_image(&self, config: &OMConfig) -> Option<String> {
+        let etag = self.offline_image.as_deref()?;
+        image_url(config, &format!("users/{}/offline", self.username), etag)
+    }
+}
+
+/// Path of the uploaded offline image of a user.
+fn offline_path(config: &OMConfig, username: &str) -> PathBuf {
+    config
+        .avatars_dir
+        .join("offline")
+        .join(format!("{username}.png"))
+}
+
+/// Upload an offline image for the currently logged in user, as the `image` field of a multipart form.
+pub async fn upload_offline_image(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    cookies: Cookies,
+    mut multipart: Multipart,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+
+    let data = read_upload(&mut multipart, "image", &config).await?;
+    let etag = store(data, WIDTH, HEIGHT, offline_path(&config, &user.username)).await?;
+    sqlx::query!(
+        "UPDATE users SET offline_image = ? WHERE username = ?",
+        etag,
+        user.username
+    )
+    .execute(&db)
+    .await?;
+
+    Ok(())
+}
+
+/// Remove the offline image of the currently logged in user.
+pub async fn delete_offline_image(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    cookies: Cookies,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+
+    sqlx::query!(
+        "UPDATE users SET offline_image = NULL WHERE username = ?",
+        user.username
+    )
+    .execute(&db)
+    .await?;
+    remove(offline_path(&config, &user.username)).await
+}
+
+/// Serve the offline image of a user. Private users' images are only served to logged in users.
+pub async fn offline_image(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    cookies: Cookies,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> Result<Response, OMError> {
+    let logged_in = User::from_req(State(db.clone()), cookies).await.is_ok();
+    let user = User::from_name(&username, &db)
+        .await
+        .filter(|u| !u.private || logged_in)
+        .ok_or(OMError::NotFound(username))?;
+    let etag = user
+        .offline_image
+        .ok_or(OMError::ItemNotFound("Offline image"))?;
+
+    serve(
+        &headers,
+        &etag,
+        offline_path(&config, &user.username),
+        user.private,
+    )
+    .await
+}
@@ -0,0 +1,265 @@
+//! Talking to the `OvenMediaEngine` REST API.
+
+use std::{collections::HashMap, time::Duration};
+
+use reqwest::{Method, RequestBuilder};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{errors::OMError, objects::OMConfig};
+
+/// How long to wait for `OvenMediaEngine` before giving up on a request.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The envelope `OvenMediaEngine` wraps every response in.
+#[derive(Debug, Deserialize)]
+struct Response<T> {
+    response: T,
+}
+
+/// Build a request to the given API path, with the access token already set.
+fn request(config: &OMConfig, method: Method, path: &str) -> RequestBuilder {
+    let mut url = config.ome_url.clone();
+    url.set_path(path);
+
+    reqwest::Client::new()
+        .request(method, url.as_str())
+        .timeout(TIMEOUT)
+        .header(
+            "authorization",
+            format!("Basic {}", base64::encode(&config.access_token)),
+        )
+}
+
+/// API path of an application.
+fn app_path(app: &str) -> String {
+    format!("v1/vhosts/default/apps/{app}")
+}
+
+/// Send a request and unwrap the `response` field of the answer.
+async fn send<T: DeserializeOwned>(req: RequestBuilder) -> Result<T, OMError> {
+    let body: Response<T> = req.send().await?.error_for_status()?.json().await?;
+    Ok(body.response)
+}
+
+/// Get the names of all streams that are currently live, in all applications of the configured profiles,
+/// together with the application they are live in.
+pub async fn streams(config: &OMConfig) -> Result<Vec<(String, String)>, OMError> {
+    let mut streams = Vec::new();
+    for app in config.apps() {
+        let names: Vec<String> = send(request(
+            config,
+            Method::GET,
+            &format!("{}/streams", app_path(app)),
+        ))
+        .await?;
+        streams.extend(names.into_iter().map(|name| (name, app.to_string())));
+    }
+    Ok(streams)
+}
+
+/// A stream that is pushed to an external server.
+#[derive(Debug, Deserialize)]
+pub struct Push {
+    /// The id that was given when starting the push.
+    pub id: String,
+    /// The state of the push, like `pushing` or `error`.
+    pub state: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StartPush<'a> {
+    id: &'a str,
+    stream: PushStream<'a>,
+    protocol: &'static str,
+    url: &'a str,
+    stream_key: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct PushStream<'a> {
+    name: &'a str,
+}
+
+/// Identifies a push or recording that should be stopped.
+#[derive(Debug, Serialize)]
+struct StopRequest<'a> {
+    id: &'a str,
+}
+
+/// Start pushing a stream to an external RTMP server.
+pub async fn start_push(
+    config: &OMConfig,
+    app: &str,
+    id: &str,
+    stream: &str,
+    url: &str,
+    stream_key: &str,
+) -> Result<(), OMError> {
+    let body = StartPush {
+        id,
+        stream: PushStream { name: stream },
+        protocol: "rtmp",
+        url,
+        stream_key,
+    };
+    request(
+        config,
+        Method::POST,
+        &format!("{}:startPush", app_path(app)),
+    )
+    .json(&body)
+    .send()
+    .await?
+    .error_for_status()?;
+    Ok(())
+}
+
+/// Stop pushing a stream.
+pub async fn stop_push(config: &OMConfig, app: &str, id: &str) -> Result<(), OMError> {
+    request(config, Method::POST, &format!("{}:stopPush", app_path(app)))
+        .json(&StopRequest { id })
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Get all pushes of an application that are currently known to `OvenMediaEngine`.
+pub async fn pushes(config: &OMConfig, app: &str) -> Result<Vec<Push>, OMError> {
+    send(request(
+        config,
+        Method::POST,
+        &format!("{}:pushes", app_path(app)),
+    ))
+    .await
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StartRecord<'a> {
+    id: &'a str,
+    stream: PushStream<'a>,
+    file_path: &'a str,
+}
+
+/// Start recording a stream into the given file.
+pub async fn start_record(
+    config: &OMConfig,
+    app: &str,
+    id: &str,
+    stream: &str,
+    file_path: &str,
+) -> Result<(), OMError> {
+    let body = StartRecord {
+        id,
+        stream: PushStream { name: stream },
+        file_path,
+    };
+    request(
+        config,
+        Method::POST,
+        &format!("{}:startRecord", app_path(app)),
+    )
+    .json(&body)
+    .send()
+    .await?
+    .error_for_status()?;
+    Ok(())
+}
+
+/// Stop recording a stream.
+pub async fn stop_record(config: &OMConfig, app: &str, id: &str) -> Result<(), OMError> {
+    request(
+        config,
+        Method::POST,
+        &format!("{}:stopRecord", app_path(app)),
+    )
+    .json(&StopRequest { id })
+    .send()
+    .await?
+    .error_for_status()?;
+    Ok(())
+}
+
+/// The current statistics of a stream.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamStats {
+    /// Number of viewers connected over any protocol.
+    pub total_connections: u64,
+    /// Number of viewers per protocol, like `webrtc` or `llhls`.
+    #[serde(default)]
+    pub connections: HashMap<String, u64>,
+    /// The incoming bitrate in bits per second.
+    #[serde(default)]
+    pub last_throughput_in: u64,
+}
+
+/// Get the current statistics of a stream.
+pub async fn stream_stats(
+    config: &OMConfig,
+    app: &str,
+    stream: &str,
+) -> Result<StreamStats, OMError> {
+    send(request(
+        config,
+        Method::GET,
+        &format!("v1/stats/current/vhosts/default/apps/{app}/streams/{stream}"),
+    ))
+    .await
+}
+
+/// Information about a stream.
+#[derive(Debug, Deserialize)]
+pub struct StreamInfo {
+    /// The incoming stream.
+    pub input: StreamInput,
+}
+
+/// The incoming stream, as published by the streamer.
+#[derive(Debug, Deserialize)]
+pub struct StreamInput {
+    /// The audio and video tracks.
+    #[serde(default)]
+    pub tracks: Vec<Track>,
+}
+
+/// A track of a stream.
+#[derive(Debug, Deserialize)]
+pub struct Track {
+    /// Only set for video tracks.
+    pub video: Option<VideoTrack>,
+}
+
+/// A video track of a stream.
+#[derive(Debug, Deserialize)]
+pub struct VideoTrack {
+    /// Frames per second.
+    pub framerate: Option<f64>,
+}
+
+impl StreamInfo {
+    /// The frame rate of the first video track.
+    #[must_use]
+    pub fn framerate(&self) -> Option<f64> {
+        self.input
+            .tracks
+            .iter()
+            .find_map(|t| t.video.as_ref()?.framerate)
+    }
+}
+
+/// Get information about a stream, like its tracks.
+pub async fn stream_info(
+    config: &OMConfig,
+    app: &str,
+    stream: &str,
+) -> Result<StreamInfo, OMError> {
+    send(request(
+        config,
+        Method::GET,
+        &format!("{}/streams/{stream}", app_path(app)),
+    ))
+    .await
+}
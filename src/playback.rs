@@ -0,0 +1,188 @@
+//! Password protected streams, which viewers can only play with a short-lived playback grant.
+//!
+//! Viewers get a grant by posting the stream password. For the protection to work, `OvenMediaEngine` has to
+//! send playback requests to the admission webhook too, which only allows them with a valid grant.
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{Duration, Utc};
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::{Arc, Mutex, PoisonError},
+    time::Instant,
+};
+use tokio::task::spawn_blocking;
+use tower_cookies::Cookies;
+
+use crate::{
+    audit::{self, ClientIp},
+    crypto::{random_data, verify_password},
+    errors::OMError,
+    ingest::{parse_playback, Playback},
+    objects::{
+        Admission, AdmissionResponse, AdmissionStatus, AppState, AuditAction, PlaybackGrant,
+        StreamPassword, User,
+    },
+    registry::resolve,
+    Db,
+};
+
+/// How long a grant can be used to open new connections, in minutes.
+const GRANT_TTL: i64 = 10;
+/// How many passwords can be tried per address and stream within [`ATTEMPT_WINDOW`].
+const ATTEMPT_LIMIT: usize = 5;
+/// The window for the password attempt limit.
+const ATTEMPT_WINDOW: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// An address and the stream it tries passwords for.
+type AttemptKey = (Option<IpAddr>, String);
+
+/// The recent password attempts per address and stream, to slow down guessing.
+#[derive(Debug, Clone, Default)]
+pub struct PasswordAttempts(Arc<Mutex<HashMap<AttemptKey, VecDeque<Instant>>>>);
+
+impl PasswordAttempts {
+    /// Check whether an address may try another password for a stream, counting it if so.
+    fn allow(&self, address: Option<IpAddr>, stream: &str) -> bool {
+        let mut attempts = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        attempts.retain(|_, tried| {
+            while tried
+                .front()
+                .is_some_and(|t| now.duration_since(*t) > ATTEMPT_WINDOW)
+            {
+                tried.pop_front();
+            }
+            !tried.is_empty()
+        });
+        let tried = attempts.entry((address, stream.to_string())).or_default();
+        if tried.len() >= ATTEMPT_LIMIT {
+            return false;
+        }
+        tried.push_back(now);
+        true
+    }
+}
+
+/// Check whether a grant allows playing the streams of a user.
+async fn is_valid(grant: &str, username: &str, db: &Db) -> bool {
+    let now = Utc::now().naive_utc();
+    let granted = sqlx::query!(
+        "SELECT user_id FROM playback_grants WHERE token = ? AND expires_at > ?",
+        grant,
+        now
+    )
+    .fetch_optional(db)
+    .await;
+    matches!(granted, Ok(Some(g)) if g.user_id.eq_ignore_ascii_case(username))
+}
+
+/// Whether a viewer may play the streams of `owner` without a grant.
+fn is_trusted(viewer: &User, owner: &User) -> bool {
+    viewer.username == owner.username || viewer.is_admin()
+}
+
+/// Check whether a viewer may access a stream of `owner`, for playing it or using its chat, viewers and statistics.
+///
+/// Password protected streams need a valid grant, except for the owner and admins. Guest streams have no owner and no password.
+pub async fn check_access(
+    owner: Option<&User>,
+    viewer: Option<&User>,
+    grant: Option<&str>,
+    db: &Db,
+) -> Result<(), OMError> {
+    let Some(owner) = owner.filter(|o| o.stream_password.is_some()) else {
+        return Ok(());
+    };
+    if viewer.is_some_and(|v| is_trusted(v, owner)) {
+        return Ok(());
+    }
+    match grant {
+        Some(grant) if is_valid(grant, &owner.username, db).await => Ok(()),
+        _ => Err(OMError::PasswordProtected),
+    }
+}
+
+/// Handle a playback admission request. Password protected streams are only allowed with a valid grant.
+pub async fn admission(state: &AppState, adm: &Admission) -> AdmissionResponse {
+    // OvenMediaEngine ignores the response for closed connections
+    if adm.status() == AdmissionStatus::Closing {
+        return AdmissionResponse::deny();
+    }
+    let Some(Playback { stream, grant }) = parse_playback(adm.borrow_url()) else {
+        return AdmissionResponse::deny();
+    };
+    let Ok((_, owner)) = resolve(&stream, true, &state.db).await else {
+        return AdmissionResponse::deny();
+    };
+
+    match check_access(owner.as_ref(), None, grant.as_deref(), &state.db).await {
+        Ok(()) => AdmissionResponse::allow(adm.borrow_url().clone()),
+        Err(_) => AdmissionResponse::deny(),
+    }
+}
+
+/// Get a playback grant for a password protected stream.
+///
+/// The owner of the stream and admins don't need the password. Wrong passwords are written to the audit log.
+pub async fn grant(
+    State(db): State<Db>,
+    State(attempts): State<PasswordAttempts>,
+    ClientIp(ip): ClientIp,
+    cookies: Cookies,
+    Path(stream): Path<String>,
+    Json(body): Json<StreamPassword>,
+) -> Result<Json<PlaybackGrant>, OMError> {
+    let viewer = User::from_req(State(db.clone()), cookies).await.ok();
+    let owner = resolve(&stream, viewer.is_some(), &db)
+        .await?
+        .1
+        .ok_or(OMError::ItemNotFound("Stream password"))?;
+    let hash = owner
+        .stream_password
+        .clone()
+        .ok_or(OMError::ItemNotFound("Stream password"))?;
+
+    if !viewer.as_ref().is_some_and(|v| is_trusted(v, &owner)) {
+        if !attempts.allow(ip, &owner.username) {
+            return Err(OMError::RateLimited);
+        }
+        let password = body.password.ok_or(OMError::InvalidPassword)?;
+        let verified = spawn_blocking(move || verify_password(&hash, password.as_bytes())).await?;
+        if verified.is_err() {
+            audit::log(
+                &db,
+                viewer.as_ref().map(|v| v.username.as_str()),
+                Some(&owner.username),
+                AuditAction::StreamPasswordFailed,
+                None,
+                ip,
+            )
+            .await;
+        }
+        verified?;
+    }
+
+    let now = Utc::now().naive_utc();
+    let expires_at = now + Duration::minutes(GRANT_TTL);
+    let token = base64::encode_config(random_data(32), base64::URL_SAFE_NO_PAD);
+    sqlx::query!("DELETE FROM playback_grants WHERE expires_at <= ?", now)
+        .execute(&db)
+        .await?;
+    sqlx::query!(
+        "INSERT INTO playback_grants (token, user_id, expires_at) VALUES (?, ?, ?)",
+        token,
+        owner.username,
+        expires_at
+    )
+    .execute(&db)
+    .await?;
+
+    Ok(Json(PlaybackGrant {
+        grant: token,
+        expires_at,
+    }))
+}
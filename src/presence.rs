@@ -0,0 +1,203 @@
+//! Which viewers are currently watching a stream, kept alive by heartbeats of the player.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use cookie::SameSite;
+use tower_cookies::{Cookie, Cookies};
+
+use crate::{
+    audit::ClientIp,
+    crypto::random_data,
+    errors::OMError,
+    objects::{AppState, GrantQuery, User, Viewer, ViewerList},
+    ome,
+    playback::check_access,
+    registry::resolve,
+};
+
+/// How long a viewer is listed after their last heartbeat.
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// The cookie that identifies anonymous viewers, so they are only counted once.
+const VIEWER_COOKIE: &str = "om_viewer";
+/// How many anonymous viewers of a stream are counted per address, for clients that don't keep the cookie.
+const MAX_ANONYMOUS_PER_ADDRESS: usize = 5;
+
+#[derive(Debug)]
+struct Presence {
+    /// The logged in user, or None for anonymous viewers.
+    viewer: Option<Viewer>,
+    /// Address the heartbeats come from.
+    address: Option<IpAddr>,
+    seen: Instant,
+}
+
+/// Shared handle to the viewers of all streams.
+#[derive(Debug, Clone, Default)]
+pub struct ViewerPresence(Arc<Mutex<HashMap<String, HashMap<String, Presence>>>>);
+
+impl ViewerPresence {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, HashMap<String, Presence>>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Mark a viewer as watching a stream. `id` identifies the viewer across heartbeats.
+    ///
+    /// New anonymous viewers are ignored once [`MAX_ANONYMOUS_PER_ADDRESS`] are watching from the same address.
+    pub fn heartbeat(
+        &self,
+        stream: &str,
+        id: String,
+        viewer: Option<Viewer>,
+        address: Option<IpAddr>,
+    ) {
+        let now = Instant::now();
+        let mut streams = self.lock();
+        // Forget everyone who stopped watching, so streams nobody watches don't pile up
+        streams.retain(|_, viewers| {
+            viewers.retain(|_, p| now.duration_since(p.seen) < TIMEOUT);
+            !viewers.is_empty()
+        });
+        let viewers = streams.entry(stream.to_string()).or_default();
+        if viewer.is_none() && !viewers.contains_key(&id) {
+            let same_address = viewers
+                .values()
+                .filter(|p| p.viewer.is_none() && p.address == address)
+                .count();
+            if same_address >= MAX_ANONYMOUS_PER_ADDRESS {
+                return;
+            }
+        }
+        viewers.insert(
+            id,
+            Presence {
+                viewer,
+                address,
+                seen: now,
+            },
+        );
+    }
+
+    /// Get the logged in viewers of a stream sorted by display name, and the number of anonymous viewers.
+    #[must_use]
+    pub fn viewers(&self, stream: &str) -> (Vec<Viewer>, usize) {
+        let now = Instant::now();
+        let streams = self.lock();
+        let mut viewers = Vec::new();
+        let mut anonymous = 0;
+        let active = streams
+            .get(stream)
+            .into_iter()
+            .flat_map(HashMap::values)
+            .filter(|p| now.duration_since(p.seen) < TIMEOUT);
+        for presence in active {
+            match &presence.viewer {
+                Some(viewer) => viewers.push(viewer.clone()),
+                None => anonymous += 1,
+            }
+        }
+        viewers.sort_by(|a, b| a.display_name.cmp(&b.display_name));
+        (viewers, anonymous)
+    }
+}
+
+/// Register the current viewer as watching a stream. Players should call this about every 15 seconds.
+///
+/// Anonymous viewers get a cookie, so that they are only counted once. Only live streams can be watched.
+pub async fn heartbeat(
+    State(state): State<AppState>,
+    Path(stream): Path<String>,
+    Query(query): Query<GrantQuery>,
+    ClientIp(ip): ClientIp,
+    cookies: Cookies,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(state.db.clone()), cookies.clone())
+        .await
+        .ok();
+    let (stream, owner) = resolve(&stream, user.is_some(), &state.db).await?;
+    if !state.registry.is_live(&stream) {
+        return Err(OMError::NotLive);
+    }
+    check_access(
+        owner.as_ref(),
+        user.as_ref(),
+        query.grant.as_deref(),
+        &state.db,
+    )
+    .await?;
+
+    let (id, viewer) = match user {
+        Some(user) => (
+            format!("user:{}", user.username),
+            Some(Viewer {
+                username: user.username,
+                display_name: user.display_name,
+            }),
+        ),
+        None => {
+            let id = match cookies.get(VIEWER_COOKIE) {
+                Some(cookie) => cookie.value().to_string(),
+                None => {
+                    let id = base64::encode(random_data(16));
+                    let cookie = Cookie::build(VIEWER_COOKIE, id.clone())
+                        .path("/")
+                        .http_only(true)
+                        .same_site(SameSite::Lax)
+                        .secure(true)
+                        .finish();
+                    cookies.add(cookie);
+                    id
+                }
+            };
+            (format!("anonymous:{id}"), None)
+        }
+    };
+
+    state.presence.heartbeat(&stream, id, viewer, ip);
+    Ok(())
+}
+
+/// List the current viewers of a stream. Anonymous viewers are only counted.
+///
+/// The number of connections `OvenMediaEngine` reports is included as a cross-check,
+/// because viewers whose player doesn't send heartbeats aren't listed.
+pub async fn viewers(
+    State(state): State<AppState>,
+    Path(stream): Path<String>,
+    Query(query): Query<GrantQuery>,
+    cookies: Cookies,
+) -> Result<Json<ViewerList>, OMError> {
+    let user = User::from_req(State(state.db.clone()), cookies).await.ok();
+    let (stream, owner) = resolve(&stream, user.is_some(), &state.db).await?;
+    check_access(
+        owner.as_ref(),
+        user.as_ref(),
+        query.grant.as_deref(),
+        &state.db,
+    )
+    .await?;
+
+    let (viewers, anonymous) = state.presence.viewers(&stream);
+    // Only live streams have connections, in the application they were admitted into
+    let connections = match state.registry.get(&stream) {
+        Some(live) => ome::stream_stats(&state.config, &live.app, &stream)
+            .await
+            .ok()
+            .map(|s| s.total_connections),
+        None => None,
+    };
+
+    Ok(Json(ViewerList {
+        viewers,
+        anonymous,
+        connections,
+    }))
+}
@@ -0,0 +1,122 @@
+//! Recording streams with `OvenMediaEngine`.
+
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::{
+    errors::OMError,
+    objects::{OMConfig, Recording, User},
+    ome, Db,
+};
+
+/// How often starting an automatic recording is tried, since the stream only exists in `OvenMediaEngine` shortly after admission.
+const ATTEMPTS: u32 = 5;
+/// Delay between attempts to start an automatic recording.
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Find the recording of the user that is still running.
+pub async fn active(username: &str, db: &Db) -> Result<Option<Recording>, sqlx::Error> {
+    sqlx::query_as!(
+        Recording,
+        "SELECT * FROM recordings WHERE user_id = ? AND ended_at IS NULL",
+        username
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Start recording the stream of a user, which is live in the application `app`.
+pub async fn start(
+    db: &Db,
+    config: &OMConfig,
+    user: &User,
+    app: &str,
+) -> Result<Recording, OMError> {
+    if active(&user.username, db).await?.is_some() {
+        return Err(OMError::AlreadyRecording);
+    }
+
+    let file_name = format!(
+        "{}_{}.mp4",
+        user.username,
+        Utc::now().format("%Y%m%d_%H%M%S")
+    );
+    let file_path = config.recordings_dir.join(file_name);
+    let file_path = file_path.to_string_lossy();
+    let recording = sqlx::query_as!(
+        Recording,
+        "INSERT INTO recordings (user_id, title, file_path) VALUES(?, ?, ?) RETURNING *",
+        user.username,
+        user.stream_title,
+        file_path
+    )
+    .fetch_one(db)
+    .await?;
+
+    if let Err(e) = ome::start_record(
+        config,
+        app,
+        &recording.record_id(),
+        &user.username,
+        &recording.file_path,
+    )
+    .await
+    {
+        sqlx::query!("DELETE FROM recordings WHERE id = ?", recording.id)
+            .execute(db)
+            .await?;
+        return Err(e);
+    }
+
+    Ok(recording)
+}
+
+/// Stop the running recording of a user, whose stream is in the application `app`, and store the final size and duration.
+pub async fn stop(db: &Db, config: &OMConfig, user: &User, app: &str) -> Result<(), OMError> {
+    let recording = active(&user.username, db)
+        .await?
+        .ok_or(OMError::ItemNotFound("Active recording"))?;
+
+    // The recording stops by itself when the stream ends
+    let _ = ome::stop_record(config, app, &recording.record_id()).await;
+
+    let size = tokio::fs::metadata(&recording.file_path)
+        .await
+        .ok()
+        .and_then(|m| i64::try_from(m.len()).ok());
+    sqlx::query!(
+        "
+        UPDATE recordings
+        SET ended_at = datetime('now'),
+            duration = strftime('%s', 'now') - strftime('%s', started_at),
+            size = ?
+        WHERE id = ?
+        ",
+        size,
+        recording.id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Start recording the user's stream in the application `app` if they enabled automatic recording.
+pub async fn auto_start(db: Db, config: OMConfig, user: User, app: String) {
+    if !user.auto_record {
+        return;
+    }
+    for _ in 0..ATTEMPTS {
+        tokio::time::sleep(RETRY_DELAY).await;
+        match start(&db, &config, &user, &app).await {
+            Ok(_) | Err(OMError::AlreadyRecording) => return,
+            Err(_) => (),
+        }
+    }
+}
+
+/// Finish the user's running recording in the application `app`, if there is one.
+pub async fn auto_stop(db: Db, config: OMConfig, user: User, app: String) {
+    let _ = stop(&db, &config, &user, &app).await;
+}
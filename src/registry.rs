@@ -0,0 +1,318 @@
+//! An in-memory registry of the streams that are currently live.
+//!
+//! The registry is kept up to date by the admission webhook and by a background task that periodically polls `OvenMediaEngine`,
+//! so that listing the streams never has to wait for `OvenMediaEngine` to answer.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::Duration,
+};
+
+use chrono::{NaiveDateTime, Utc};
+
+use crate::{
+    broadcasts, channels,
+    errors::OMError,
+    events::StreamEvent,
+    guests,
+    objects::{AppState, User},
+    ome, recording, restream, Db,
+};
+
+/// How long a stream admitted by the webhook stays live without `OvenMediaEngine` listing it, in seconds.
+const ADMISSION_GRACE: i64 = 30;
+
+/// A stream that is currently live.
+#[derive(Debug, Clone)]
+pub struct LiveStream {
+    /// Name of the stream, which is the username of the streaming user or `username_channel` for channels.
+    pub name: String,
+    /// The `OvenMediaEngine` application the stream was admitted into.
+    pub app: String,
+    /// Time the stream was first seen in UTC.
+    pub started_at: NaiveDateTime,
+    /// Whether `OvenMediaEngine` has listed the stream yet.
+    reported: bool,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    streams: HashMap<String, LiveStream>,
+    stale: bool,
+}
+
+/// Shared handle to the registry of live streams.
+#[derive(Debug, Clone, Default)]
+pub struct StreamRegistry(Arc<RwLock<Registry>>);
+
+impl StreamRegistry {
+    fn read(&self) -> RwLockReadGuard<'_, Registry> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Registry> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Mark a stream as live in the given application. Returns `false` if the stream was already known.
+    pub fn open(&self, name: &str, app: &str) -> bool {
+        let mut registry = self.write();
+        if registry.streams.contains_key(name) {
+            return false;
+        }
+        registry.streams.insert(
+            name.to_string(),
+            LiveStream {
+                name: name.to_string(),
+                app: app.to_string(),
+                started_at: Utc::now().naive_utc(),
+                reported: false,
+            },
+        );
+        true
+    }
+
+    /// Remove a stream from the registry. Returns the stream if it was live.
+    pub fn close(&self, name: &str) -> Option<LiveStream> {
+        self.write().streams.remove(name)
+    }
+
+    /// Replace the registry with the streams `OvenMediaEngine` reports as pairs of name and application,
+    /// keeping the start time of known streams.
+    ///
+    /// Streams that were admitted less than [`ADMISSION_GRACE`] seconds ago are kept until `OvenMediaEngine` lists them,
+    /// since the listing may have been taken before the admission.
+    ///
+    /// Returns the names of the streams that started and the streams that ended since the last update.
+    pub fn sync(&self, names: Vec<(String, String)>) -> (Vec<String>, Vec<LiveStream>) {
+        let mut registry = self.write();
+        let now = Utc::now().naive_utc();
+        let mut started = Vec::new();
+        let mut streams: HashMap<String, LiveStream> = names
+            .into_iter()
+            .map(|(name, app)| {
+                let mut stream = registry.streams.remove(&name).unwrap_or_else(|| {
+                    started.push(name.clone());
+                    LiveStream {
+                        name: name.clone(),
+                        app,
+                        started_at: now,
+                        reported: true,
+                    }
+                });
+                stream.reported = true;
+                (name, stream)
+            })
+            .collect();
+        let mut ended = Vec::new();
+        for (name, stream) in std::mem::take(&mut registry.streams) {
+            if !stream.reported
+                && now - stream.started_at < chrono::Duration::seconds(ADMISSION_GRACE)
+            {
+                streams.insert(name, stream);
+            } else {
+                ended.push(stream);
+            }
+        }
+        registry.streams = streams;
+        registry.stale = false;
+        (started, ended)
+    }
+
+    /// Flag the registry as stale, because `OvenMediaEngine` couldn't be reached.
+    pub fn mark_stale(&self) {
+        self.write().stale = true;
+    }
+
+    /// Whether the last attempt to reach `OvenMediaEngine` failed.
+    #[must_use]
+    pub fn is_stale(&self) -> bool {
+        self.read().stale
+    }
+
+    /// Check whether the given stream is live.
+    #[must_use]
+    pub fn is_live(&self, name: &str) -> bool {
+        self.read().streams.contains_key(name)
+    }
+
+    /// Get a live stream by name.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<LiveStream> {
+        self.read().streams.get(name).cloned()
+    }
+
+    /// Get all live streams, oldest first.
+    #[must_use]
+    pub fn streams(&self) -> Vec<LiveStream> {
+        let mut streams: Vec<LiveStream> = self.read().streams.values().cloned().collect();
+        streams.sort_by_key(|s| s.started_at);
+        streams
+    }
+}
+
+/// Find the name of a stream as it is used in `OvenMediaEngine`, and the user it belongs to.
+/// Guest streams don't belong to anybody and can only be found while they are live.
+///
+/// Returns [`OMError::NotFound`] if there is no such stream, and for private streams if `logged_in` isn't set.
+pub async fn resolve(
+    stream: &str,
+    logged_in: bool,
+    db: &Db,
+) -> Result<(String, Option<User>), OMError> {
+    if let Some(user) = User::from_name(stream, db).await {
+        if !user.private || logged_in {
+            return Ok((user.username.clone(), Some(user)));
+        }
+    } else if let Some(channel) = channels::by_stream_name(stream, db).await {
+        // Channels of private users are private too
+        let owner = User::from_name(&channel.user_id, db).await;
+        if !(channel.private || owner.as_ref().is_some_and(|o| o.private)) || logged_in {
+            return Ok((channel.stream_name(), owner));
+        }
+    } else if let Some(guest) = guests::streaming(stream, db).await {
+        return Ok((guest.path, None));
+    }
+    Err(OMError::NotFound(stream.to_string()))
+}
+
+/// Whether a user exists and is private. Guests are never private.
+async fn is_private(username: &str, db: &Db) -> bool {
+    User::from_name(username, db)
+        .await
+        .is_some_and(|u| u.private)
+}
+
+/// Clean up after a stream went offline, whether the admission webhook or [`poll`] noticed it first.
+///
+/// Ends the broadcast and announces it on the [`EventBus`](crate::events::EventBus).
+/// Also stops the automatic recording and restreams of users, and closes the key of guests.
+pub async fn ended(state: &AppState, stream: LiveStream) {
+    let AppState { db, config, .. } = state;
+    let _ = broadcasts::end(db, &stream.name).await;
+    let event = if let Some(user) = User::from_name(&stream.name, db).await {
+        tokio::spawn(recording::auto_stop(
+            db.clone(),
+            config.clone(),
+            user.clone(),
+            stream.app.clone(),
+        ));
+        tokio::spawn(restream::stop(
+            db.clone(),
+            config.clone(),
+            user.clone(),
+            stream.app,
+        ));
+        StreamEvent::ended(user.username, user.private)
+    } else if let Some(channel) = channels::by_stream_name(&stream.name, db).await {
+        StreamEvent::StreamEnded {
+            private: channel.private || is_private(&channel.user_id, db).await,
+            username: channel.user_id,
+            channel: Some(channel.name),
+        }
+    } else {
+        // Guest keys can't be used again after going offline
+        if let Some(guest) = guests::streaming(&stream.name, db).await {
+            let _ = guests::close(&guest, db).await;
+        }
+        StreamEvent::ended(stream.name, false)
+    };
+    state.events.send(event);
+}
+
+/// Poll `OvenMediaEngine` for the live streams forever, at the configured interval.
+///
+/// Streams that were missed by the admission webhook are logged as broadcasts and announced on the [`EventBus`](crate::events::EventBus).
+pub async fn poll(state: AppState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(state.config.poll_interval));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        let Ok(names) = ome::streams(&state.config).await else {
+            state.registry.mark_stale();
+            continue;
+        };
+
+        let (started, ended) = state.registry.sync(names);
+        for name in started {
+            let db = &state.db;
+            if let Some(user) = User::from_name(&name, db).await {
+                let title = user.stream_title.as_deref();
+                let _ = broadcasts::start(db, None, &name, Some(&user.username), None, title).await;
+                state.events.send(StreamEvent::started(&user));
+            } else if let Some(channel) = channels::by_stream_name(&name, db).await {
+                let _ = broadcasts::start(
+                    db,
+                    None,
+                    &name,
+                    Some(&channel.user_id),
+                    Some(&channel.name),
+                    channel.title.as_deref(),
+                )
+                .await;
+                if let Some(user) = User::from_name(&channel.user_id, db).await {
+                    state
+                        .events
+                        .send(StreamEvent::channel_started(&user, &channel));
+                }
+            } else {
+                // Guests
+                let _ = broadcasts::start(db, None, &name, None, None, None).await;
+            }
+        }
+        for stream in ended {
+            self::ended(&state, stream).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(names: &[&str]) -> Vec<(String, String)> {
+        names
+            .iter()
+            .map(|n| (n.to_string(), "app".to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_admitted_streams_until_listed() {
+        let registry = StreamRegistry::default();
+        assert!(registry.open("alice", "app"));
+
+        // The listing was taken before alice was admitted
+        let (started, ended) = registry.sync(listing(&[]));
+        assert!(started.is_empty());
+        assert!(ended.is_empty());
+        assert!(registry.is_live("alice"));
+
+        let (started, ended) = registry.sync(listing(&["alice", "bob"]));
+        assert_eq!(started, ["bob"]);
+        assert!(ended.is_empty());
+
+        let (_, ended) = registry.sync(listing(&["bob"]));
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].name, "alice");
+        assert!(!registry.is_live("alice"));
+    }
+
+    #[test]
+    fn drops_admitted_streams_after_grace() {
+        let registry = StreamRegistry::default();
+        registry.open("alice", "app");
+        registry
+            .write()
+            .streams
+            .get_mut("alice")
+            .unwrap()
+            .started_at -= chrono::Duration::seconds(ADMISSION_GRACE);
+
+        let (_, ended) = registry.sync(listing(&[]));
+        assert_eq!(ended.len(), 1);
+        assert!(!registry.is_live("alice"));
+    }
+}
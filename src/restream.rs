@@ -0,0 +1,70 @@
+//! Republishing streams to external RTMP servers, like Twitch or YouTube.
+
+use std::time::Duration;
+
+use crate::{
+    crypto::decrypt,
+    objects::{OMConfig, RestreamTarget, User},
+    ome, Db,
+};
+
+/// How often starting a push is tried, since the stream only exists in `OvenMediaEngine` shortly after admission.
+const ATTEMPTS: u32 = 5;
+/// Delay between attempts to start a push.
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Get all restream targets of a user.
+pub async fn targets(username: &str, db: &Db) -> Result<Vec<RestreamTarget>, sqlx::Error> {
+    sqlx::query_as!(
+        RestreamTarget,
+        "SELECT * FROM restream_targets WHERE user_id = ? ORDER BY id",
+        username
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Start pushing the user's stream in the application `app` to all of their restream targets.
+pub async fn start(db: Db, config: OMConfig, user: User, app: String) {
+    let Some(secret) = config.secret_key.as_deref() else {
+        return;
+    };
+    let Ok(targets) = targets(&user.username, &db).await else {
+        return;
+    };
+
+    for target in targets {
+        let Ok(stream_key) = decrypt(secret, &target.stream_key) else {
+            continue;
+        };
+        let push_id = target.push_id();
+        for _ in 0..ATTEMPTS {
+            tokio::time::sleep(RETRY_DELAY).await;
+            if ome::start_push(
+                &config,
+                &app,
+                &push_id,
+                &user.username,
+                &target.url,
+                &stream_key,
+            )
+            .await
+            .is_ok()
+            {
+                break;
+            }
+        }
+    }
+}
+
+/// Stop pushing the user's stream in the application `app` to all of their restream targets.
+pub async fn stop(db: Db, config: OMConfig, user: User, app: String) {
+    let Ok(targets) = targets(&user.username, &db).await else {
+        return;
+    };
+
+    for target in targets {
+        // The push may already be gone together with the stream
+        let _ = ome::stop_push(&config, &app, &target.push_id()).await;
+    }
+}
@@ -0,0 +1,238 @@
+//! Enforcing the retention policy and storage quotas for recordings.
+
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{NaiveDateTime, Utc};
+
+use crate::{
+    errors::OMError,
+    objects::{OMConfig, Recording},
+    Db,
+};
+
+/// Delete a recording and its file. Files that are already gone are ignored.
+pub async fn delete(recording: &Recording, db: &Db) -> Result<(), OMError> {
+    if let Err(e) = tokio::fs::remove_file(&recording.file_path).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            return Err(e.into());
+        }
+    }
+    sqlx::query!("DELETE FROM recordings WHERE id = ?", recording.id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Pick the recordings that violate the retention policy at `now`, oldest first. Pinned recordings are never picked.
+///
+/// `recordings` have to be sorted from oldest to newest.
+#[must_use]
+pub fn expired<'a>(
+    recordings: &'a [Recording],
+    config: &OMConfig,
+    now: NaiveDateTime,
+) -> Vec<&'a Recording> {
+    let size = |r: &Recording| u64::try_from(r.size.unwrap_or_default()).unwrap_or_default();
+    let mut doomed = vec![false; recordings.len()];
+
+    if let Some(max_age) = config.retention_max_age {
+        let max_age = chrono::Duration::days(i64::try_from(max_age).unwrap_or(i64::MAX));
+        for (i, r) in recordings.iter().enumerate() {
+            if !r.pinned && r.ended_at.is_some_and(|end| now - end > max_age) {
+                doomed[i] = true;
+            }
+        }
+    }
+
+    if let Some(quota) = config.retention_user_quota {
+        let mut usage: HashMap<&str, u64> = HashMap::new();
+        for (i, r) in recordings.iter().enumerate() {
+            if !doomed[i] {
+                *usage.entry(&r.user_id).or_default() += size(r);
+            }
+        }
+        for (i, r) in recordings.iter().enumerate() {
+            let used = usage.entry(&r.user_id).or_default();
+            if *used > quota && !r.pinned && !doomed[i] {
+                doomed[i] = true;
+                *used -= size(r);
+            }
+        }
+    }
+
+    if let Some(max_bytes) = config.retention_max_bytes {
+        let mut used: u64 = recordings
+            .iter()
+            .zip(&doomed)
+            .filter(|(_, d)| !**d)
+            .map(|(r, _)| size(r))
+            .sum();
+        for (i, r) in recordings.iter().enumerate() {
+            if used <= max_bytes {
+                break;
+            }
+            if !r.pinned && !doomed[i] {
+                doomed[i] = true;
+                used -= size(r);
+            }
+        }
+    }
+
+    recordings
+        .iter()
+        .zip(doomed)
+        .filter_map(|(r, d)| d.then_some(r))
+        .collect()
+}
+
+/// Delete all recordings that violate the retention policy.
+///
+/// Recordings that can't be deleted are kept and tried again on the next run.
+pub async fn cleanup(db: &Db, config: &OMConfig) -> Result<(), OMError> {
+    let recordings = sqlx::query_as!(
+        Recording,
+        "SELECT * FROM recordings WHERE ended_at IS NOT NULL ORDER BY started_at"
+    )
+    .fetch_all(db)
+    .await?;
+
+    for recording in expired(&recordings, config, Utc::now().naive_utc()) {
+        let _ = delete(recording, db).await;
+    }
+    Ok(())
+}
+
+/// Enforce the retention policy forever, at the configured interval.
+pub async fn run(db: Db, config: OMConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.cleanup_interval));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        let _ = cleanup(&db, &config).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate};
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 1)
+            .and_then(|d| d.and_hms_opt(12, 0, 0))
+            .unwrap()
+    }
+
+    fn config(retention: Value) -> OMConfig {
+        let mut config = json!({
+            "ome_url": "http://localhost:8081",
+            "access_token": "",
+            "admission_key": "",
+            "base_url": "http://localhost:8080",
+            "ws_url": "ws://localhost:3333",
+        });
+        config
+            .as_object_mut()
+            .unwrap()
+            .extend(retention.as_object().unwrap().clone());
+        serde_json::from_value(config).unwrap()
+    }
+
+    /// A finished recording of `size` bytes that ended `age` before [`now`].
+    fn recording(id: i64, user_id: &str, size: i64, age: Duration) -> Recording {
+        Recording {
+            id,
+            user_id: user_id.into(),
+            title: None,
+            file_path: format!("{id}.mp4"),
+            size: Some(size),
+            duration: Some(60),
+            started_at: now() - age - Duration::minutes(1),
+            ended_at: Some(now() - age),
+            pinned: false,
+        }
+    }
+
+    fn ids(expired: &[&Recording]) -> Vec<i64> {
+        expired.iter().map(|r| r.id).collect()
+    }
+
+    #[test]
+    fn nothing_expires_without_policy() {
+        let recordings = [recording(1, "alice", 100, Duration::days(1000))];
+        assert!(expired(&recordings, &config(json!({})), now()).is_empty());
+    }
+
+    #[test]
+    fn max_age() {
+        let config = config(json!({ "retention_max_age": 7 }));
+        let mut pinned = recording(3, "alice", 1, Duration::days(30));
+        pinned.pinned = true;
+        let mut running = recording(4, "alice", 1, Duration::days(30));
+        running.ended_at = None;
+        let recordings = [
+            recording(1, "alice", 1, Duration::days(7) + Duration::seconds(1)),
+            recording(2, "alice", 1, Duration::days(7)),
+            pinned,
+            running,
+        ];
+
+        assert_eq!(ids(&expired(&recordings, &config, now())), [1]);
+    }
+
+    #[test]
+    fn user_quota() {
+        let config = config(json!({ "retention_user_quota": 100 }));
+        let mut pinned = recording(1, "alice", 40, Duration::days(4));
+        pinned.pinned = true;
+        let recordings = [
+            pinned,
+            recording(2, "alice", 40, Duration::days(3)),
+            recording(3, "alice", 40, Duration::days(2)),
+            recording(4, "alice", 20, Duration::days(1)),
+            // Exactly at the quota
+            recording(5, "bob", 60, Duration::days(2)),
+            recording(6, "bob", 40, Duration::days(1)),
+        ];
+
+        // Alice uses 140 bytes, the pinned recording is skipped and 40 bytes are enough
+        assert_eq!(ids(&expired(&recordings, &config, now())), [2]);
+    }
+
+    #[test]
+    fn total_quota() {
+        let mut pinned = recording(1, "alice", 50, Duration::days(3));
+        pinned.pinned = true;
+        let recordings = [
+            pinned,
+            recording(2, "bob", 30, Duration::days(2)),
+            recording(3, "alice", 30, Duration::days(1)),
+            recording(4, "bob", 20, Duration::hours(1)),
+        ];
+
+        // 130 bytes in total, removing the oldest unpinned recording leaves exactly 100
+        let config_100 = config(json!({ "retention_max_bytes": 100 }));
+        assert_eq!(ids(&expired(&recordings, &config_100, now())), [2]);
+        let config_130 = config(json!({ "retention_max_bytes": 130 }));
+        assert!(expired(&recordings, &config_130, now()).is_empty());
+    }
+
+    #[test]
+    fn expired_recordings_dont_count_towards_quotas() {
+        let config = config(json!({
+            "retention_max_age": 7,
+            "retention_user_quota": 50,
+            "retention_max_bytes": 60,
+        }));
+        let recordings = [
+            recording(1, "alice", 100, Duration::days(8)),
+            recording(2, "alice", 50, Duration::days(1)),
+            recording(3, "bob", 10, Duration::days(1)),
+        ];
+
+        assert_eq!(ids(&expired(&recordings, &config, now())), [1]);
+    }
+}
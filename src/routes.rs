@@ -1,47 +1,248 @@
 //! All the routes for the API.
 
-use axum::{extract::State, Json};
+use std::collections::HashMap;
+
+use axum::{
+    body::{boxed, Body},
+    extract::{Path, Query, State},
+    http::{header, HeaderValue, Request},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{NaiveDateTime, Utc};
 use cookie::{time, SameSite};
+use serde_json::json;
 use tokio::task::spawn_blocking;
+use tower::ServiceExt;
 use tower_cookies::{Cookie, Cookies};
+use tower_http::services::ServeFile;
+use url::Url;
 
 use crate::{
-    crypto::{gen_stream_key, hash_password, verify_password},
+    audit::{self, ClientIp},
+    broadcasts, channels,
+    crypto::{encrypt, gen_stream_key, hash_password, verify_password},
     errors::OMError,
+    events::{EventBus, StreamEvent},
+    guests,
+    ingest::{ingest_info, parse_publish, playback_urls, Publish},
+    notify,
     objects::{
-        Admission, AdmissionResponse, OMConfig, SendableUser, StreamResp, Streams, User, UserLogin,
-        UserUpdate,
+        Admission, AdmissionDirection, AdmissionResponse, AdmissionStatus, AppState, AuditAction,
+        Channel, ChannelCreate, ChannelResp, ChannelUpdate, Followed, GrantQuery, GuestKey,
+        GuestKeyCreate, IngestInfo, NotificationSettings, NotificationUpdate, OMConfig,
+        PublicProfile, Recording, RecordingControl, RecordingPin, RecordingQuery, RestreamCreate,
+        RestreamTarget, SearchQuery, SearchResult, SendableRestreamTarget, SendableUser,
+        StorageUsage, StreamKey, StreamKeyCreate, StreamProfile, StreamQuery, StreamResp, User,
+        UserLogin, UserUpdate, Webhook, WebhookCreate, WebhookKind,
     },
-    Db, USERNAME_RE,
+    ome, playback, recording,
+    registry::{self, LiveStream, StreamRegistry},
+    restream, search, stream_keys, Db, CHANNEL_RE, USERNAME_RE,
 };
 
+/// The maximum length of a bio in characters.
+const MAX_BIO_LEN: usize = 500;
+/// How many links a profile can have.
+const MAX_LINKS: usize = 5;
+/// The maximum length of an offline message in characters.
+const MAX_OFFLINE_MESSAGE_LEN: usize = 500;
+
 /// Handle the admission requests from the OvenMediaEngine server.
+///
+/// Allowed and closed incoming streams are recorded in the [`StreamRegistry`] and announced on the [`EventBus`].
+/// Going live also triggers the user's webhooks, restreams and automatic recording, going offline stops them.
+/// Playback requests are handled by [`playback::admission`].
 pub async fn admission(
-    State(db): State<Db>,
+    State(state): State<AppState>,
     Json(adm): Json<Admission>,
 ) -> Json<AdmissionResponse> {
-    let mut url = adm.borrow_url().clone();
-    let mut path: Vec<&str> = match url.path_segments().map(std::iter::Iterator::collect) {
-        Some(vec) => vec,
-        None => return Json(AdmissionResponse::deny()),
-    };
-    // Get the last element, which should be the stream key
-    let stream_key = path.pop().unwrap_or_default();
-    let user = sqlx::query_as!(User, "SELECT * FROM users WHERE stream_key = ?", stream_key)
-        .fetch_one(&db)
-        .await;
+    if adm.direction() == AdmissionDirection::Outgoing {
+        return Json(playback::admission(&state, &adm).await);
+    }
+    let Some(Publish { stream_key, url }) = parse_publish(adm.borrow_url(), adm.authorization())
+    else {
+        return Json(deny(&state.db, &adm, None, "no stream key").await);
+    };
+    let AppState {
+        db,
+        config,
+        registry,
+        events,
+        ..
+    } = &state;
 
-    match user {
-        Ok(user) => {
-            if !user.has_permission("CAN_STREAM") {
+    if let Some(guest) = guests::lookup(&stream_key, db).await {
+        return Json(guest_admission(&state, &adm, guest, url).await);
+    }
+    if let Some(channel) = channels::lookup(&stream_key, db).await {
+        return Json(channel_admission(&state, &adm, channel, url).await);
+    }
+
+    match stream_keys::lookup(&stream_key, db).await {
+        Some((user, key)) => {
+            let incoming = adm.direction() == AdmissionDirection::Incoming;
+            // OvenMediaEngine ignores the response for closed connections
+            if adm.status() == AdmissionStatus::Closing {
+                if let Some(stream) = incoming.then(|| registry.close(&user.username)).flatten() {
+                    registry::ended(&state, stream).await;
+                }
                 return Json(AdmissionResponse::deny());
+            }
+            if !user.has_permission("CAN_STREAM") {
+                return Json(deny(db, &adm, Some(&user.username), "missing CAN_STREAM").await);
             };
-            path.push(&user.username);
-            url.set_path(&path.join("/"));
-            Json(AdmissionResponse::allow(url))
+            if let Some(key) = key {
+                let now = Utc::now().naive_utc();
+                if !key.allows(adm.protocol(), adm.address(), now) {
+                    let reason = "stream key restrictions";
+                    return Json(deny(db, &adm, Some(&user.username), reason).await);
+                }
+                let _ = stream_keys::touch(&key, db).await;
+            }
+            // Route the stream into the application of the user's profile
+            let app = config.app_for(&user);
+            if incoming && registry.open(&user.username, app) {
+                let title = user.stream_title.as_deref();
+                let name = Some(user.username.as_str());
+                let _ = broadcasts::start(db, Some(&adm), &user.username, name, None, title).await;
+                events.send(StreamEvent::started(&user));
+                tokio::spawn(restream::start(
+                    db.clone(),
+                    config.clone(),
+                    user.clone(),
+                    app.to_string(),
+                ));
+                tokio::spawn(recording::auto_start(
+                    db.clone(),
+                    config.clone(),
+                    user.clone(),
+                    app.to_string(),
+                ));
+                tokio::spawn(notify::stream_started(
+                    db.clone(),
+                    config.clone(),
+                    user.clone(),
+                ));
+            }
+            Json(rewrite(url, incoming.then_some(app), &user.username))
+        }
+        None => Json(deny(db, &adm, None, "unknown stream key").await),
+    }
+}
+
+/// Deny a stream. Incoming streams that are being opened are noted in the audit log.
+async fn deny(db: &Db, adm: &Admission, target: Option<&str>, reason: &str) -> AdmissionResponse {
+    if adm.direction() == AdmissionDirection::Incoming && adm.status() == AdmissionStatus::Opening {
+        let diff = json!({ "reason": reason, "protocol": adm.protocol() });
+        audit::log(
+            db,
+            None,
+            target,
+            AuditAction::AdmissionDenied,
+            Some(diff),
+            adm.address(),
+        )
+        .await;
+    }
+    AdmissionResponse::deny()
+}
+
+/// Allow the stream under the given name, replacing the application at the end of `url` with `app` if given.
+///
+/// The stream key has already been removed from `url` by [`parse_publish`].
+fn rewrite(mut url: Url, app: Option<&str>, name: &str) -> AdmissionResponse {
+    let mut path: Vec<String> = match url.path_segments() {
+        Some(segments) => segments.map(String::from).collect(),
+        None => return AdmissionResponse::deny(),
+    };
+    if let (Some(segment), Some(app)) = (path.last_mut(), app) {
+        *segment = app.to_string();
+    }
+    path.push(name.to_string());
+    url.set_path(&path.join("/"));
+    AdmissionResponse::allow(url)
+}
+
+/// Handle an admission request that was made with a guest key.
+///
+/// Guest keys can only be used until they expire or the guest stops streaming for the first time.
+async fn guest_admission(
+    state: &AppState,
+    adm: &Admission,
+    guest: GuestKey,
+    url: Url,
+) -> AdmissionResponse {
+    if adm.direction() != AdmissionDirection::Incoming {
+        return AdmissionResponse::deny();
+    }
+    if adm.status() == AdmissionStatus::Closing {
+        if guest.used_at.is_some() {
+            let _ = guests::close(&guest, &state.db).await;
+            if let Some(stream) = state.registry.close(&guest.path) {
+                registry::ended(state, stream).await;
+            }
+        }
+        return AdmissionResponse::deny();
+    }
+    if !guest.is_valid(Utc::now().naive_utc()) || guests::open(&guest, &state.db).await.is_err() {
+        return deny(&state.db, adm, None, "guest key expired or used").await;
+    }
+
+    if state.registry.open(&guest.path, state.config.default_app()) {
+        let _ = broadcasts::start(&state.db, Some(adm), &guest.path, None, None, None).await;
+        state.events.send(StreamEvent::StreamStarted {
+            username: guest.path.clone(),
+            display_name: guest.display_name,
+            title: None,
+            channel: None,
+            private: false,
+        });
+    }
+    rewrite(url, Some(state.config.default_app()), &guest.path)
+}
+
+/// Handle an admission request that was made with the stream key of a channel.
+///
+/// Channels stream under `username_channel` into the application of their owner's profile.
+async fn channel_admission(
+    state: &AppState,
+    adm: &Admission,
+    channel: Channel,
+    url: Url,
+) -> AdmissionResponse {
+    let Some(user) = User::from_name(&channel.user_id, &state.db).await else {
+        return deny(&state.db, adm, None, "unknown stream key").await;
+    };
+    let incoming = adm.direction() == AdmissionDirection::Incoming;
+    let name = channel.stream_name();
+
+    if adm.status() == AdmissionStatus::Closing {
+        if let Some(stream) = incoming.then(|| state.registry.close(&name)).flatten() {
+            registry::ended(state, stream).await;
         }
-        Err(_) => Json(AdmissionResponse::deny()),
+        return AdmissionResponse::deny();
+    }
+    if !user.has_permission("CAN_STREAM") {
+        return deny(&state.db, adm, Some(&user.username), "missing CAN_STREAM").await;
+    };
+
+    let app = state.config.app_for(&user);
+    if incoming && state.registry.open(&name, app) {
+        let _ = broadcasts::start(
+            &state.db,
+            Some(adm),
+            &name,
+            Some(&user.username),
+            Some(&channel.name),
+            channel.title.as_deref(),
+        )
+        .await;
+        state
+            .events
+            .send(StreamEvent::channel_started(&user, &channel));
     }
+    rewrite(url, incoming.then_some(app), &name)
 }
 
 /// Get the currently logged in user.
@@ -56,6 +257,7 @@ pub async fn user(State(db): State<Db>, cookies: Cookies) -> Result<Json<Sendabl
 pub async fn login(
     State(db): State<Db>,
     cookies: Cookies,
+    ClientIp(ip): ClientIp,
     Json(creds): Json<UserLogin>,
 ) -> Result<(), OMError> {
     // Check if a session already exists
@@ -66,11 +268,20 @@ pub async fn login(
         }
     }
 
-    let user = User::from_name(&creds.username, &db)
-        .await
-        .ok_or(OMError::NotFound(creds.username))?;
+    let Some(user) = User::from_name(&creds.username, &db).await else {
+        let target = Some(creds.username.as_str());
+        audit::log(&db, None, target, AuditAction::LoginFailed, None, ip).await;
+        return Err(OMError::NotFound(creds.username));
+    };
 
-    spawn_blocking(move || verify_password(&user.password, creds.password.as_bytes())).await??;
+    let hash = user.password.clone();
+    let verified =
+        spawn_blocking(move || verify_password(&hash, creds.password.as_bytes())).await?;
+    if let Err(e) = verified {
+        let target = Some(user.username.as_str());
+        audit::log(&db, None, target, AuditAction::LoginFailed, None, ip).await;
+        return Err(e.into());
+    }
 
     let token = base64::encode(crate::crypto::random_data(64));
     sqlx::query!(
@@ -90,11 +301,18 @@ pub async fn login(
         .finish();
     cookies.add(session_cookie);
 
+    let name = Some(user.username.as_str());
+    audit::log(&db, name, name, AuditAction::Login, None, ip).await;
+
     Ok(())
 }
 
 /// Remove the session cookie.
-pub async fn logout(State(db): State<Db>, cookies: Cookies) -> Result<(), OMError> {
+pub async fn logout(
+    State(db): State<Db>,
+    cookies: Cookies,
+    ClientIp(ip): ClientIp,
+) -> Result<(), OMError> {
     let Some(om_cookie) = cookies.get("om_session") else {
         return Ok(());
     };
@@ -103,6 +321,10 @@ pub async fn logout(State(db): State<Db>, cookies: Cookies) -> Result<(), OMErro
         .parse::<String>()
         .map_err(|_| OMError::InvalidSession)?;
 
+    if let Some(user) = User::from_session(&token, &db).await {
+        let name = Some(user.username.as_str());
+        audit::log(&db, name, name, AuditAction::Logout, None, ip).await;
+    }
     sqlx::query!("DELETE FROM sessions WHERE session = ?", token)
         .execute(&db)
         .await?;
@@ -113,7 +335,11 @@ pub async fn logout(State(db): State<Db>, cookies: Cookies) -> Result<(), OMErro
 }
 
 /// Register a new user, making sure that the username is valid.
-pub async fn register(State(db): State<Db>, Json(creds): Json<UserLogin>) -> Result<(), OMError> {
+pub async fn register(
+    State(db): State<Db>,
+    ClientIp(ip): ClientIp,
+    Json(creds): Json<UserLogin>,
+) -> Result<(), OMError> {
     if User::from_name(&creds.username, &db).await.is_some() {
         return Err(OMError::NameTaken);
     };
@@ -122,6 +348,17 @@ pub async fn register(State(db): State<Db>, Json(creds): Json<UserLogin>) -> Res
         .is_match(&creds.username)
         .then_some(())
         .ok_or(OMError::InvalidUsername)?;
+    // Guests stream under their path, which must not be mistaken for a user
+    if guests::path_taken(&creds.username, &db).await? {
+        return Err(OMError::NameTaken);
+    }
+    // Channels stream as `username_channel`, which must not be mistaken for another user
+    if channels::by_stream_name(&creds.username, &db)
+        .await
+        .is_some()
+    {
+        return Err(OMError::NameTaken);
+    };
 
     let hashed_password =
         tokio::task::spawn_blocking(move || hash_password(creds.password.as_bytes())).await??;
@@ -138,6 +375,9 @@ pub async fn register(State(db): State<Db>, Json(creds): Json<UserLogin>) -> Res
     .execute(&db)
     .await?;
 
+    let name = Some(creds.username.as_str());
+    audit::log(&db, name, name, AuditAction::Register, None, ip).await;
+
     Ok(())
 }
 
@@ -167,7 +407,10 @@ pub async fn list_users(
 /// Update a user.
 pub async fn update_user(
     State(db): State<Db>,
+    State(config): State<OMConfig>,
+    State(events): State<EventBus>,
     cookies: Cookies,
+    ClientIp(ip): ClientIp,
     Json(body): Json<UserUpdate>,
 ) -> Result<(), OMError> {
     let performing_user = User::from_req(State(db.clone()), cookies).await?;
@@ -203,6 +446,12 @@ pub async fn update_user(
         )
         .execute(&db)
         .await?;
+        events.send(StreamEvent::TitleChanged {
+            username: user.username.clone(),
+            title: Some(stream_title.clone()),
+            channel: None,
+            private: body.private.unwrap_or(user.private),
+        });
     };
 
     let new_password: Option<String> = match (body.old_password.clone(), body.new_password.clone())
@@ -231,6 +480,173 @@ pub async fn update_user(
         )
         .execute(&db)
         .await?;
+        let action = if body.old_password.is_some() {
+            AuditAction::PasswordChanged
+        } else {
+            AuditAction::PasswordReset
+        };
+        let (actor, target) = (performing_user.username.as_str(), user.username.as_str());
+        audit::log(&db, Some(actor), Some(target), action, None, ip).await;
+    };
+
+    if let Some(stream_profile) = &body.stream_profile {
+        if !config.profiles.iter().any(|p| &p.name == stream_profile) {
+            return Err(OMError::InvalidProfile(stream_profile.clone()));
+        }
+        sqlx::query!(
+            "UPDATE users SET stream_profile = ? WHERE username = ?",
+            stream_profile,
+            user.username
+        )
+        .execute(&db)
+        .await?;
+    };
+
+    if let Some(category) = &body.category {
+        let category = search::category(category)?;
+        sqlx::query!(
+            "UPDATE users SET category = ? WHERE username = ?",
+            category,
+            user.username
+        )
+        .execute(&db)
+        .await?;
+    };
+
+    if let Some(tags) = &body.tags {
+        let tags = search::tags(tags)?;
+        sqlx::query!(
+            "UPDATE users SET tags = ? WHERE username = ?",
+            tags,
+            user.username
+        )
+        .execute(&db)
+        .await?;
+    };
+
+    if let Some(bio) = &body.bio {
+        let bio = bio.trim();
+        if bio.chars().count() > MAX_BIO_LEN {
+            return Err(OMError::InvalidProfileField);
+        }
+        let bio = (!bio.is_empty()).then_some(bio);
+        sqlx::query!(
+            "UPDATE users SET bio = ? WHERE username = ?",
+            bio,
+            user.username
+        )
+        .execute(&db)
+        .await?;
+    };
+
+    if let Some(links) = &body.links {
+        if links.len() > MAX_LINKS {
+            return Err(OMError::InvalidProfileField);
+        }
+        if links
+            .iter()
+            .any(|l| !matches!(l.scheme(), "http" | "https"))
+        {
+            return Err(OMError::InvalidUrl);
+        }
+        let links = (!links.is_empty())
+            .then(|| links.iter().map(Url::as_str).collect::<Vec<_>>().join(" "));
+        sqlx::query!(
+            "UPDATE users SET links = ? WHERE username = ?",
+            links,
+            user.username
+        )
+        .execute(&db)
+        .await?;
+    };
+
+    if let Some(avatar_url) = &body.avatar_url {
+        let avatar_url = match avatar_url.trim() {
+            "" => None,
+            url => match Url::parse(url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => Some(url.to_string()),
+                _ => return Err(OMError::InvalidUrl),
+            },
+        };
+        sqlx::query!(
+            "UPDATE users SET avatar_url = ? WHERE username = ?",
+            avatar_url,
+            user.username
+        )
+        .execute(&db)
+        .await?;
+    };
+
+    if let Some(offline_message) = &body.offline_message {
+        let offline_message = offline_message.trim();
+        if offline_message.chars().count() > MAX_OFFLINE_MESSAGE_LEN {
+            return Err(OMError::InvalidOfflineMessage);
+        }
+        let offline_message = (!offline_message.is_empty()).then_some(offline_message);
+        sqlx::query!(
+            "UPDATE users SET offline_message = ? WHERE username = ?",
+            offline_message,
+            user.username
+        )
+        .execute(&db)
+        .await?;
+    };
+
+    if let Some(stream_password) = &body.stream_password {
+        let hash = match stream_password.as_str() {
+            "" => None,
+            password => {
+                let password = password.to_string();
+                Some(
+                    tokio::task::spawn_blocking(move || hash_password(password.as_bytes()))
+                        .await??,
+                )
+            }
+        };
+        let diff = json!({ "protected": hash.is_some() });
+        sqlx::query!(
+            "UPDATE users SET stream_password = ? WHERE username = ?",
+            hash,
+            user.username
+        )
+        .execute(&db)
+        .await?;
+        // Grants for the old password must not work anymore
+        sqlx::query!(
+            "DELETE FROM playback_grants WHERE user_id = ?",
+            user.username
+        )
+        .execute(&db)
+        .await?;
+        audit::log(
+            &db,
+            Some(&performing_user.username),
+            Some(&user.username),
+            AuditAction::StreamPasswordChanged,
+            Some(diff),
+            ip,
+        )
+        .await;
+    };
+
+    if let Some(private) = body.private {
+        sqlx::query!(
+            "UPDATE users SET private = ? WHERE username = ?",
+            private,
+            user.username
+        )
+        .execute(&db)
+        .await?;
+    };
+
+    if let Some(auto_record) = body.auto_record {
+        sqlx::query!(
+            "UPDATE users SET auto_record = ? WHERE username = ?",
+            auto_record,
+            user.username
+        )
+        .execute(&db)
+        .await?;
     };
 
     if performing_user.is_admin() {
@@ -242,6 +658,16 @@ pub async fn update_user(
             )
             .execute(&db)
             .await?;
+            let diff = json!({ "permissions": { "old": user.permissions, "new": permissions } });
+            audit::log(
+                &db,
+                Some(&performing_user.username),
+                Some(&user.username),
+                AuditAction::PermissionsChanged,
+                Some(diff),
+                ip,
+            )
+            .await;
         }
     } else if body.permissions.is_some() {
         return Err(OMError::NoPermission);
@@ -250,42 +676,1089 @@ pub async fn update_user(
     Ok(())
 }
 
-/// Get all currently active streams.
-pub async fn streams(
+/// Get the public profile of a user. Private users are only visible to logged in users.
+pub async fn profile(
     State(db): State<Db>,
     State(config): State<OMConfig>,
-) -> Result<Json<Vec<StreamResp>>, OMError> {
-    let mut url = config.ome_url.clone();
-    url.set_path("v1/vhosts/default/apps/stream/streams");
-
-    let client = reqwest::Client::new();
-    let body: Streams = client
-        .get(url.as_str())
-        .header(
-            "authorization",
-            format!("Basic {}", base64::encode(&config.access_token)),
-        )
-        .send()
-        .await?
-        .json()
-        .await?;
+    State(registry): State<StreamRegistry>,
+    cookies: Cookies,
+    Path(username): Path<String>,
+) -> Result<Json<PublicProfile>, OMError> {
+    let logged_in = User::from_req(State(db.clone()), cookies).await.is_ok();
+    let user = User::from_name(&username, &db)
+        .await
+        .filter(|u| !u.private || logged_in)
+        .ok_or(OMError::NotFound(username))?;
+
+    let last_streamed = last_streamed(&user.username, &db).await?;
+    let live = registry.is_live(&user.username);
+
+    Ok(Json(PublicProfile::new(user, &config, live, last_streamed)))
+}
+
+/// Time the last broadcast of a user ended, or started if it is still live.
+async fn last_streamed(username: &str, db: &Db) -> Result<Option<NaiveDateTime>, OMError> {
+    let last_streamed = sqlx::query!(
+        r#"
+        SELECT MAX(COALESCE(ended_at, started_at)) as "last_streamed: NaiveDateTime"
+        FROM broadcasts WHERE user_id = ?
+        "#,
+        username
+    )
+    .fetch_one(db)
+    .await?
+    .last_streamed;
+    Ok(last_streamed)
+}
+
+/// The stream of a user, without any live channels.
+fn user_stream(user: &User, config: &OMConfig) -> StreamResp {
+    StreamResp {
+        username: user.username.clone(),
+        display_name: user.display_name.clone(),
+        title: user.stream_title.clone(),
+        thumbnail: config
+            .base_url
+            .join(&format!("streams/{}/thumbnail", user.username))
+            .ok(),
+        avatar: user.avatar(config),
+        category: user.category.clone(),
+        tags: user.tags().map(String::from).collect(),
+        guest: false,
+        live: false,
+        app: None,
+        playback: None,
+        password_protected: user.stream_password.is_some(),
+        channels: Vec::new(),
+        offline_message: None,
+        offline_image: None,
+        last_live: None,
+    }
+}
+
+/// The stream of a live guest.
+fn guest_stream(guest: GuestKey, live: &LiveStream, config: &OMConfig) -> StreamResp {
+    StreamResp {
+        playback: playback_urls(config, &live.app, &guest.path),
+        app: Some(live.app.clone()),
+        username: guest.path,
+        display_name: guest.display_name,
+        title: None,
+        thumbnail: None,
+        avatar: None,
+        category: None,
+        tags: Vec::new(),
+        guest: true,
+        live: true,
+        password_protected: false,
+        channels: Vec::new(),
+        offline_message: None,
+        offline_image: None,
+        last_live: None,
+    }
+}
 
-    // Return early if there are no streams
-    if body.response.is_empty() {
-        return Ok(Json(Vec::new()));
+/// Find the entry of a user in the list of streams, adding an offline one if there is none yet.
+fn stream_entry<'a>(
+    streams: &'a mut Vec<StreamResp>,
+    user: &User,
+    config: &OMConfig,
+) -> &'a mut StreamResp {
+    let pos = streams
+        .iter()
+        .position(|s| !s.guest && s.username == user.username);
+    let pos = pos.unwrap_or_else(|| {
+        streams.push(user_stream(user, config));
+        streams.len() - 1
+    });
+    &mut streams[pos]
+}
+
+/// Mark the main stream of a user as live in the application it was admitted into.
+fn set_live(stream: &mut StreamResp, live: &LiveStream, config: &OMConfig) {
+    stream.live = true;
+    stream.app = Some(live.app.clone());
+    stream.playback = playback_urls(config, &live.app, &live.name);
+}
+
+/// A live channel, as listed with the stream of its owner.
+fn channel_resp(channel: Channel, live: &LiveStream, config: &OMConfig) -> ChannelResp {
+    ChannelResp {
+        stream: channel.stream_name(),
+        playback: playback_urls(config, &live.app, &live.name),
+        name: channel.name,
+        title: channel.title,
     }
+}
+
+/// Get all currently active streams from the [`StreamRegistry`], without waiting for OvenMediaEngine.
+///
+/// Private streams are only listed for logged in users.
+/// The channels of a user are grouped together with their main stream.
+/// Streams can be filtered by category and tag, which excludes guests.
+/// If OvenMediaEngine couldn't be reached the last time it was polled, the list may be outdated,
+/// which is signalled with a `Warning: 110` header.
+pub async fn streams(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    State(registry): State<StreamRegistry>,
+    cookies: Cookies,
+    Query(query): Query<StreamQuery>,
+) -> Result<Response, OMError> {
+    let live = registry.streams();
+    let stale = registry.is_stale();
+    let logged_in = User::from_req(State(db.clone()), cookies).await.is_ok();
 
     let mut streams: Vec<StreamResp> = Vec::new();
     // SQLx sadly doesn't support IN queries, so we have to do this the hard way
-    for s in body.response {
-        match User::from_name(&s, &db).await {
-            Some(u) => streams.push(StreamResp {
-                username: u.username,
-                display_name: u.display_name,
-                title: u.stream_title,
-            }),
-            None => (),
+    for s in live {
+        if let Some(u) = User::from_name(&s.name, &db).await {
+            if (!u.private || logged_in) && query.matches(&u) {
+                set_live(stream_entry(&mut streams, &u, &config), &s, &config);
+            }
+        } else if let Some(c) = channels::by_stream_name(&s.name, &db).await {
+            // Channels of private users are private too
+            if let Some(u) = User::from_name(&c.user_id, &db)
+                .await
+                .filter(|u| (!(c.private || u.private) || logged_in) && query.matches(u))
+            {
+                stream_entry(&mut streams, &u, &config)
+                    .channels
+                    .push(channel_resp(c, &s, &config));
+            }
+        } else if query.is_filtered() {
+            continue;
+        } else if let Some(g) = guests::streaming(&s.name, &db).await {
+            streams.push(guest_stream(g, &s, &config));
+        }
+    }
+
+    let mut response = Json(streams).into_response();
+    if stale {
+        response.headers_mut().insert(
+            header::WARNING,
+            HeaderValue::from_static("110 - \"Response is Stale\""),
+        );
+    }
+    Ok(response)
+}
+
+/// Get the stream of a single user or guest.
+///
+/// While the user isn't live on any of their streams, their offline message and image are included,
+/// together with the time they were last live.
+pub async fn stream(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    State(registry): State<StreamRegistry>,
+    cookies: Cookies,
+    Path(username): Path<String>,
+) -> Result<Json<StreamResp>, OMError> {
+    let logged_in = User::from_req(State(db.clone()), cookies).await.is_ok();
+    let Some(user) = User::from_name(&username, &db)
+        .await
+        .filter(|u| !u.private || logged_in)
+    else {
+        // Guests can only be found while they are live
+        let guest = guests::streaming(&username, &db).await;
+        return match guest.and_then(|g| registry.get(&g.path).map(|s| (g, s))) {
+            Some((g, s)) => Ok(Json(guest_stream(g, &s, &config))),
+            None => Err(OMError::NotFound(username)),
         };
+    };
+
+    let mut stream = user_stream(&user, &config);
+    if let Some(s) = registry.get(&user.username) {
+        set_live(&mut stream, &s, &config);
     }
-    Ok(Json(streams))
+    for s in registry.streams() {
+        if let Some(c) = channels::by_stream_name(&s.name, &db).await {
+            if c.user_id.eq_ignore_ascii_case(&user.username) && (!c.private || logged_in) {
+                stream.channels.push(channel_resp(c, &s, &config));
+            }
+        }
+    }
+    if !stream.live && stream.channels.is_empty() {
+        stream.offline_image = user.offline_image(&config);
+        stream.offline_message = user.offline_message;
+        stream.last_live = last_streamed(&user.username, &db).await?;
+    }
+
+    Ok(Json(stream))
+}
+
+/// Search users by their name, stream title, category and tags.
+///
+/// Private users are only found by logged in users.
+pub async fn search(
+    State(db): State<Db>,
+    State(registry): State<StreamRegistry>,
+    cookies: Cookies,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<Vec<SearchResult>>, OMError> {
+    let logged_in = User::from_req(State(db.clone()), cookies).await.is_ok();
+
+    let results = search::search(&query.q, logged_in, &db)
+        .await?
+        .into_iter()
+        .map(|u| SearchResult {
+            live: registry.is_live(&u.username),
+            tags: u.tags().map(String::from).collect(),
+            username: u.username,
+            display_name: u.display_name,
+            title: u.stream_title,
+            category: u.category,
+        })
+        .collect();
+
+    Ok(Json(results))
+}
+
+/// List the webhooks of the currently logged in user, and the global webhooks if the user may manage them.
+pub async fn list_webhooks(
+    State(db): State<Db>,
+    cookies: Cookies,
+) -> Result<Json<Vec<Webhook>>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    let global = user.has_permission("CAN_GLOBAL_WEBHOOKS");
+
+    let webhooks = sqlx::query_as!(
+        Webhook,
+        r#"
+        SELECT id, user_id, url, kind as "kind: WebhookKind", template, created_at FROM webhooks
+        WHERE user_id = ? OR (user_id IS NULL AND ?)
+        ORDER BY id
+        "#,
+        user.username,
+        global
+    )
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(webhooks))
+}
+
+/// Add a webhook for the currently logged in user, or a global one with the `CAN_GLOBAL_WEBHOOKS` permission.
+pub async fn create_webhook(
+    State(db): State<Db>,
+    cookies: Cookies,
+    Json(body): Json<WebhookCreate>,
+) -> Result<Json<Webhook>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    if body.global && !user.has_permission("CAN_GLOBAL_WEBHOOKS") {
+        return Err(OMError::NoPermission);
+    };
+    notify::check_target(&body.url).await?;
+
+    let owner = (!body.global).then_some(user.username);
+    let url = body.url.as_str();
+    let webhook = sqlx::query_as!(
+        Webhook,
+        r#"
+        INSERT INTO webhooks (user_id, url, kind, template) VALUES(?, ?, ?, ?)
+        RETURNING id, user_id, url, kind as "kind: WebhookKind", template, created_at
+        "#,
+        owner,
+        url,
+        body.kind,
+        body.template
+    )
+    .fetch_one(&db)
+    .await?;
+
+    Ok(Json(webhook))
+}
+
+/// Delete a webhook. Users can only delete their own webhooks and the global ones if they may manage them, admins can delete any.
+pub async fn delete_webhook(
+    State(db): State<Db>,
+    cookies: Cookies,
+    ClientIp(ip): ClientIp,
+    Path(id): Path<i64>,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    let global = user.has_permission("CAN_GLOBAL_WEBHOOKS");
+    let is_admin = user.is_admin();
+
+    let deleted = sqlx::query!(
+        "DELETE FROM webhooks WHERE id = ? AND (user_id = ? OR (user_id IS NULL AND ?) OR ?)",
+        id,
+        user.username,
+        global,
+        is_admin
+    )
+    .execute(&db)
+    .await?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(OMError::ItemNotFound("Webhook"));
+    }
+    let diff = Some(json!({ "id": id }));
+    let actor = Some(user.username.as_str());
+    audit::log(&db, actor, None, AuditAction::WebhookDeleted, diff, ip).await;
+    Ok(())
+}
+
+/// List the streamers the currently logged in user follows, with their live status.
+pub async fn list_follows(
+    State(db): State<Db>,
+    State(registry): State<StreamRegistry>,
+    cookies: Cookies,
+) -> Result<Json<Vec<Followed>>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+
+    let follows = sqlx::query!(
+        "
+        SELECT users.username, users.display_name, users.stream_title, follows.created_at FROM follows
+        JOIN users
+        ON follows.streamer = users.username
+        WHERE follows.user_id = ?
+        ORDER BY users.display_name
+        ",
+        user.username
+    )
+    .fetch_all(&db)
+    .await?
+    .into_iter()
+    .map(|f| Followed {
+        live: registry.is_live(&f.username),
+        username: f.username,
+        display_name: f.display_name,
+        title: f.stream_title,
+        followed_at: f.created_at,
+    })
+    .collect();
+
+    Ok(Json(follows))
+}
+
+/// Follow a streamer.
+pub async fn follow(
+    State(db): State<Db>,
+    cookies: Cookies,
+    Path(username): Path<String>,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    let streamer = User::from_name(&username, &db)
+        .await
+        .ok_or(OMError::NotFound(username))?;
+
+    sqlx::query!(
+        "INSERT OR IGNORE INTO follows (user_id, streamer) VALUES(?, ?)",
+        user.username,
+        streamer.username
+    )
+    .execute(&db)
+    .await?;
+
+    Ok(())
+}
+
+/// Stop following a streamer.
+pub async fn unfollow(
+    State(db): State<Db>,
+    cookies: Cookies,
+    Path(username): Path<String>,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+
+    sqlx::query!(
+        "DELETE FROM follows WHERE user_id = ? AND streamer = ? COLLATE NOCASE",
+        user.username,
+        username
+    )
+    .execute(&db)
+    .await?;
+
+    Ok(())
+}
+
+/// Get the notification settings of the currently logged in user.
+pub async fn notification_settings(
+    State(db): State<Db>,
+    cookies: Cookies,
+) -> Result<Json<NotificationSettings>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+
+    let settings = sqlx::query_as!(
+        NotificationSettings,
+        "SELECT browser, webhook_url FROM notification_settings WHERE user_id = ?",
+        user.username
+    )
+    .fetch_optional(&db)
+    .await?
+    .unwrap_or_default();
+
+    Ok(Json(settings))
+}
+
+/// Change the notification settings of the currently logged in user.
+pub async fn update_notification_settings(
+    State(db): State<Db>,
+    cookies: Cookies,
+    Json(body): Json<NotificationUpdate>,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    if let Some(url) = &body.webhook_url {
+        notify::check_target(url).await?;
+    };
+
+    let webhook_url = body.webhook_url.as_ref().map(Url::as_str);
+    sqlx::query!(
+        "
+        INSERT INTO notification_settings (user_id, browser, webhook_url) VALUES(?, ?, ?)
+        ON CONFLICT (user_id) DO UPDATE SET browser = excluded.browser, webhook_url = excluded.webhook_url
+        ",
+        user.username,
+        body.browser,
+        webhook_url
+    )
+    .execute(&db)
+    .await?;
+
+    Ok(())
+}
+
+/// List the restream targets of the currently logged in user, with the state of their pushes.
+pub async fn list_restreams(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    State(registry): State<StreamRegistry>,
+    cookies: Cookies,
+) -> Result<Json<Vec<SendableRestreamTarget>>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    let targets = restream::targets(&user.username, &db).await?;
+
+    // Only ask OvenMediaEngine if there can be any pushes at all
+    let pushes = match registry.get(&user.username) {
+        Some(stream) => ome::pushes(&config, &stream.app).await.unwrap_or_default(),
+        None => Vec::new(),
+    };
+
+    let targets = targets
+        .into_iter()
+        .map(|t| {
+            let push_id = t.push_id();
+            SendableRestreamTarget {
+                id: t.id,
+                name: t.name,
+                url: t.url,
+                state: pushes
+                    .iter()
+                    .find(|p| p.id == push_id)
+                    .map(|p| p.state.clone()),
+            }
+        })
+        .collect();
+
+    Ok(Json(targets))
+}
+
+/// Add a restream target for the currently logged in user. The stream key is stored encrypted.
+pub async fn create_restream(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    cookies: Cookies,
+    Json(body): Json<RestreamCreate>,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    let secret = config.secret_key.as_deref().ok_or(OMError::NoSecretKey)?;
+    if !matches!(body.url.scheme(), "rtmp" | "rtmps") {
+        return Err(OMError::InvalidRtmpUrl);
+    };
+
+    let url = body.url.as_str();
+    let stream_key = encrypt(secret, &body.stream_key)?;
+    sqlx::query!(
+        "INSERT INTO restream_targets (user_id, name, url, stream_key) VALUES(?, ?, ?, ?)",
+        user.username,
+        body.name,
+        url,
+        stream_key
+    )
+    .execute(&db)
+    .await?;
+
+    Ok(())
+}
+
+/// Delete a restream target of the currently logged in user, stopping its push.
+pub async fn delete_restream(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    State(registry): State<StreamRegistry>,
+    cookies: Cookies,
+    ClientIp(ip): ClientIp,
+    Path(id): Path<i64>,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+
+    let target = sqlx::query_as!(
+        RestreamTarget,
+        "DELETE FROM restream_targets WHERE id = ? AND user_id = ? RETURNING *",
+        id,
+        user.username
+    )
+    .fetch_optional(&db)
+    .await?
+    .ok_or(OMError::ItemNotFound("Restream target"))?;
+
+    if let Some(stream) = registry.get(&user.username) {
+        let _ = ome::stop_push(&config, &stream.app, &target.push_id()).await;
+    }
+    let diff = Some(json!({ "id": id, "name": target.name }));
+    let name = Some(user.username.as_str());
+    audit::log(&db, name, name, AuditAction::RestreamDeleted, diff, ip).await;
+    Ok(())
+}
+
+/// Find the user whose stream should be controlled. Only admins may control the streams of other users.
+async fn target_user(
+    performing_user: User,
+    username: Option<String>,
+    db: &Db,
+) -> Result<User, OMError> {
+    match username {
+        Some(u) if u != performing_user.username => {
+            if !performing_user.is_admin() {
+                return Err(OMError::NoPermission);
+            }
+            User::from_name(&u, db).await.ok_or(OMError::NotFound(u))
+        }
+        _ => Ok(performing_user),
+    }
+}
+
+/// Start recording a live stream.
+pub async fn start_recording(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    State(registry): State<StreamRegistry>,
+    cookies: Cookies,
+    Json(body): Json<RecordingControl>,
+) -> Result<Json<Recording>, OMError> {
+    let performing_user = User::from_req(State(db.clone()), cookies).await?;
+    let user = target_user(performing_user, body.username, &db).await?;
+    let stream = registry.get(&user.username).ok_or(OMError::NotLive)?;
+
+    let recording = recording::start(&db, &config, &user, &stream.app).await?;
+    Ok(Json(recording))
+}
+
+/// Stop the running recording of a stream.
+pub async fn stop_recording(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    State(registry): State<StreamRegistry>,
+    cookies: Cookies,
+    Json(body): Json<RecordingControl>,
+) -> Result<(), OMError> {
+    let performing_user = User::from_req(State(db.clone()), cookies).await?;
+    let user = target_user(performing_user, body.username, &db).await?;
+    // The stream may have ended already
+    let app = registry
+        .get(&user.username)
+        .map_or_else(|| config.app_for(&user).to_string(), |s| s.app);
+
+    recording::stop(&db, &config, &user, &app).await
+}
+
+/// List all recordings, newest first. Recordings of private streams are only listed for logged in users,
+/// and those of password protected streams only with a playback grant.
+pub async fn recordings(
+    State(db): State<Db>,
+    cookies: Cookies,
+    Query(query): Query<RecordingQuery>,
+    Query(grant): Query<GrantQuery>,
+) -> Result<Json<Vec<Recording>>, OMError> {
+    let viewer = User::from_req(State(db.clone()), cookies).await.ok();
+    let logged_in = viewer.is_some();
+
+    let recordings = sqlx::query_as!(
+        Recording,
+        "
+        SELECT recordings.* FROM recordings
+        JOIN users
+        ON recordings.user_id = users.username
+        WHERE (?1 IS NULL OR recordings.user_id = ?1 COLLATE NOCASE)
+        AND (NOT users.private OR ?2)
+        ORDER BY recordings.started_at DESC
+        ",
+        query.username,
+        logged_in
+    )
+    .fetch_all(&db)
+    .await?;
+
+    // Check every user only once
+    let mut allowed: HashMap<String, bool> = HashMap::new();
+    let mut visible = Vec::new();
+    for recording in recordings {
+        let ok = match allowed.get(&recording.user_id) {
+            Some(ok) => *ok,
+            None => {
+                let owner = User::from_name(&recording.user_id, &db).await;
+                let grant = grant.grant.as_deref();
+                let ok = playback::check_access(owner.as_ref(), viewer.as_ref(), grant, &db)
+                    .await
+                    .is_ok();
+                allowed.insert(recording.user_id.clone(), ok);
+                ok
+            }
+        };
+        if ok {
+            visible.push(recording);
+        }
+    }
+
+    Ok(Json(visible))
+}
+
+/// Serve the file of a finished recording, supporting range requests for seeking.
+///
+/// Recordings of password protected streams need a playback grant.
+pub async fn recording_file(
+    State(db): State<Db>,
+    cookies: Cookies,
+    Path(id): Path<i64>,
+    Query(query): Query<GrantQuery>,
+    req: Request<Body>,
+) -> Result<Response, OMError> {
+    let viewer = User::from_req(State(db.clone()), cookies).await.ok();
+    let logged_in = viewer.is_some();
+
+    let recording = sqlx::query_as!(
+        Recording,
+        "
+        SELECT recordings.* FROM recordings
+        JOIN users
+        ON recordings.user_id = users.username
+        WHERE recordings.id = ? AND recordings.ended_at IS NOT NULL
+        AND (NOT users.private OR ?)
+        ",
+        id,
+        logged_in
+    )
+    .fetch_optional(&db)
+    .await?
+    .ok_or(OMError::ItemNotFound("Recording"))?;
+    let owner = User::from_name(&recording.user_id, &db).await;
+    playback::check_access(owner.as_ref(), viewer.as_ref(), query.grant.as_deref(), &db).await?;
+
+    let res = match ServeFile::new(&recording.file_path).oneshot(req).await {
+        Ok(res) => res,
+        Err(never) => match never {},
+    };
+    Ok(res.map(boxed))
+}
+
+/// Pin or unpin a recording. Users can only pin their own recordings, admins can pin any.
+pub async fn pin_recording(
+    State(db): State<Db>,
+    cookies: Cookies,
+    Path(id): Path<i64>,
+    Json(body): Json<RecordingPin>,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    let is_admin = user.is_admin();
+
+    let updated = sqlx::query!(
+        "UPDATE recordings SET pinned = ? WHERE id = ? AND (user_id = ? OR ?)",
+        body.pinned,
+        id,
+        user.username,
+        is_admin
+    )
+    .execute(&db)
+    .await?
+    .rows_affected();
+
+    if updated == 0 {
+        return Err(OMError::ItemNotFound("Recording"));
+    }
+    Ok(())
+}
+
+/// Report how much storage the recordings of each user take up, biggest first. Admin only.
+pub async fn storage_usage(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    cookies: Cookies,
+) -> Result<Json<Vec<StorageUsage>>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    if !user.is_admin() {
+        return Err(OMError::NoPermission);
+    };
+
+    let usage = sqlx::query!(
+        r#"
+        SELECT user_id,
+            COUNT(*) as "recordings!: i64",
+            COALESCE(SUM(size), 0) as "bytes!: i64",
+            COALESCE(SUM(CASE WHEN pinned THEN size END), 0) as "pinned_bytes!: i64"
+        FROM recordings
+        GROUP BY user_id
+        ORDER BY 3 DESC
+        "#
+    )
+    .fetch_all(&db)
+    .await?
+    .into_iter()
+    .map(|u| StorageUsage {
+        username: u.user_id,
+        recordings: u.recordings,
+        bytes: u.bytes,
+        pinned_bytes: u.pinned_bytes,
+        quota: config.retention_user_quota,
+    })
+    .collect();
+
+    Ok(Json(usage))
+}
+
+/// List the stream profiles users can choose from.
+pub async fn profiles(State(config): State<OMConfig>) -> Json<Vec<StreamProfile>> {
+    Json(config.profiles)
+}
+
+/// Get the ingest and playback urls of the currently logged in user.
+pub async fn ingest(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    cookies: Cookies,
+) -> Result<Json<IngestInfo>, OMError> {
+    let user = User::from_req(State(db), cookies).await?;
+    let info = ingest_info(&config, &user).ok_or(OMError::InvalidConfigUrl)?;
+    Ok(Json(info))
+}
+
+/// List the additional stream keys of the currently logged in user.
+pub async fn list_stream_keys(
+    State(db): State<Db>,
+    cookies: Cookies,
+) -> Result<Json<Vec<StreamKey>>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+
+    let keys = sqlx::query_as!(
+        StreamKey,
+        "SELECT * FROM stream_keys WHERE user_id = ? ORDER BY id",
+        user.username
+    )
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(keys))
+}
+
+/// Create an additional stream key for the currently logged in user.
+pub async fn create_stream_key(
+    State(db): State<Db>,
+    cookies: Cookies,
+    ClientIp(ip): ClientIp,
+    Json(body): Json<StreamKeyCreate>,
+) -> Result<Json<StreamKey>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    if body.expires_at.is_some_and(|e| e <= Utc::now().naive_utc()) {
+        return Err(OMError::InvalidExpiry);
+    };
+
+    let stream_key = gen_stream_key();
+    // Empty lists would lock the key out completely, so they don't restrict it
+    let protocols = body
+        .protocols
+        .filter(|p| !p.is_empty())
+        .map(|p| p.join(",").to_lowercase());
+    let cidrs = body.cidrs.filter(|c| !c.is_empty()).map(|c| {
+        c.iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    });
+    let key = sqlx::query_as!(
+        StreamKey,
+        "
+        INSERT INTO stream_keys (user_id, stream_key, label, expires_at, protocols, cidrs)
+        VALUES(?, ?, ?, ?, ?, ?)
+        RETURNING *
+        ",
+        user.username,
+        stream_key,
+        body.label,
+        body.expires_at,
+        protocols,
+        cidrs
+    )
+    .fetch_one(&db)
+    .await?;
+
+    let diff = Some(json!({ "id": key.id, "label": key.label }));
+    let name = Some(user.username.as_str());
+    audit::log(&db, name, name, AuditAction::StreamKeyCreated, diff, ip).await;
+
+    Ok(Json(key))
+}
+
+/// Revoke an additional stream key of the currently logged in user.
+pub async fn delete_stream_key(
+    State(db): State<Db>,
+    cookies: Cookies,
+    ClientIp(ip): ClientIp,
+    Path(id): Path<i64>,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+
+    let deleted = sqlx::query!(
+        "DELETE FROM stream_keys WHERE id = ? AND user_id = ?",
+        id,
+        user.username
+    )
+    .execute(&db)
+    .await?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(OMError::ItemNotFound("Stream key"));
+    }
+    let diff = Some(json!({ "id": id }));
+    let name = Some(user.username.as_str());
+    audit::log(&db, name, name, AuditAction::StreamKeyDeleted, diff, ip).await;
+    Ok(())
+}
+
+/// List guest keys. Admins get all keys, other users only the ones they created.
+pub async fn list_guest_keys(
+    State(db): State<Db>,
+    cookies: Cookies,
+) -> Result<Json<Vec<GuestKey>>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    let is_admin = user.is_admin();
+
+    let keys = sqlx::query_as!(
+        GuestKey,
+        "
+        SELECT * FROM guest_keys
+        WHERE created_by = ? OR ?
+        ORDER BY created_at DESC
+        ",
+        user.username,
+        is_admin
+    )
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(keys))
+}
+
+/// Create a guest key. Requires the `CAN_INVITE` permission.
+pub async fn create_guest_key(
+    State(db): State<Db>,
+    cookies: Cookies,
+    ClientIp(ip): ClientIp,
+    Json(body): Json<GuestKeyCreate>,
+) -> Result<Json<GuestKey>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    if !user.has_permission("CAN_INVITE") {
+        return Err(OMError::NoPermission);
+    };
+
+    USERNAME_RE
+        .is_match(&body.path)
+        .then_some(())
+        .ok_or(OMError::InvalidUsername)?;
+    if User::from_name(&body.path, &db).await.is_some()
+        || channels::by_stream_name(&body.path, &db).await.is_some()
+        || guests::path_taken(&body.path, &db).await?
+    {
+        return Err(OMError::NameTaken);
+    };
+    if body.expires_at <= Utc::now().naive_utc() {
+        return Err(OMError::InvalidExpiry);
+    };
+
+    let stream_key = gen_stream_key();
+    let key = sqlx::query_as!(
+        GuestKey,
+        "
+        INSERT INTO guest_keys (stream_key, display_name, path, created_by, expires_at)
+        VALUES(?, ?, ?, ?, ?)
+        RETURNING *
+        ",
+        stream_key,
+        body.display_name,
+        body.path,
+        user.username,
+        body.expires_at
+    )
+    .fetch_one(&db)
+    .await?;
+
+    let diff = Some(json!({ "id": key.id, "path": key.path, "expires_at": key.expires_at }));
+    let actor = Some(user.username.as_str());
+    audit::log(&db, actor, None, AuditAction::GuestKeyCreated, diff, ip).await;
+
+    Ok(Json(key))
+}
+
+/// Revoke a guest key. Users can only revoke the keys they created, admins can revoke any.
+pub async fn delete_guest_key(
+    State(db): State<Db>,
+    cookies: Cookies,
+    ClientIp(ip): ClientIp,
+    Path(id): Path<i64>,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    let is_admin = user.is_admin();
+
+    let deleted = sqlx::query!(
+        "DELETE FROM guest_keys WHERE id = ? AND (created_by = ? OR ?)",
+        id,
+        user.username,
+        is_admin
+    )
+    .execute(&db)
+    .await?
+    .rows_affected();
+
+    if deleted == 0 {
+        return Err(OMError::ItemNotFound("Guest key"));
+    }
+    let diff = Some(json!({ "id": id }));
+    let actor = Some(user.username.as_str());
+    audit::log(&db, actor, None, AuditAction::GuestKeyDeleted, diff, ip).await;
+    Ok(())
+}
+
+/// List the channels of the currently logged in user, including their stream keys.
+pub async fn list_channels(
+    State(db): State<Db>,
+    cookies: Cookies,
+) -> Result<Json<Vec<Channel>>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+
+    let channels = sqlx::query_as!(
+        Channel,
+        "SELECT * FROM channels WHERE user_id = ? ORDER BY name",
+        user.username
+    )
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(channels))
+}
+
+/// Create a channel for the currently logged in user, which gets its own stream key.
+pub async fn create_channel(
+    State(db): State<Db>,
+    cookies: Cookies,
+    ClientIp(ip): ClientIp,
+    Json(body): Json<ChannelCreate>,
+) -> Result<Json<Channel>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    if !user.has_permission("CAN_STREAM") {
+        return Err(OMError::NoPermission);
+    };
+
+    CHANNEL_RE
+        .is_match(&body.name)
+        .then_some(())
+        .ok_or(OMError::InvalidChannelName)?;
+    // The stream name of the channel must not be mistaken for another user
+    let stream_name = format!("{}_{}", user.username, body.name);
+    if channels::by_name(&user.username, &body.name, &db)
+        .await
+        .is_some()
+        || User::from_name(&stream_name, &db).await.is_some()
+        || guests::path_taken(&stream_name, &db).await?
+    {
+        return Err(OMError::NameTaken);
+    };
+
+    let stream_key = gen_stream_key();
+    let channel = sqlx::query_as!(
+        Channel,
+        "
+        INSERT INTO channels (user_id, name, stream_key, title, private)
+        VALUES(?, ?, ?, ?, ?)
+        RETURNING *
+        ",
+        user.username,
+        body.name,
+        stream_key,
+        body.title,
+        body.private
+    )
+    .fetch_one(&db)
+    .await?;
+
+    let diff = Some(json!({ "channel": channel.name }));
+    let name = Some(user.username.as_str());
+    audit::log(&db, name, name, AuditAction::ChannelCreated, diff, ip).await;
+
+    Ok(Json(channel))
+}
+
+/// Update the title or visibility of a channel of the currently logged in user.
+pub async fn update_channel(
+    State(db): State<Db>,
+    State(events): State<EventBus>,
+    cookies: Cookies,
+    Path(name): Path<String>,
+    Json(body): Json<ChannelUpdate>,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    let channel = channels::by_name(&user.username, &name, &db)
+        .await
+        .ok_or(OMError::ItemNotFound("Channel"))?;
+
+    if let Some(title) = &body.title {
+        sqlx::query!(
+            "UPDATE channels SET title = ? WHERE id = ?",
+            title,
+            channel.id
+        )
+        .execute(&db)
+        .await?;
+        events.send(StreamEvent::TitleChanged {
+            username: user.username,
+            title: Some(title.clone()),
+            channel: Some(channel.name),
+            private: user.private || body.private.unwrap_or(channel.private),
+        });
+    };
+
+    if let Some(private) = body.private {
+        sqlx::query!(
+            "UPDATE channels SET private = ? WHERE id = ?",
+            private,
+            channel.id
+        )
+        .execute(&db)
+        .await?;
+    };
+
+    Ok(())
+}
+
+/// Delete a channel of the currently logged in user, together with its stream key.
+pub async fn delete_channel(
+    State(db): State<Db>,
+    cookies: Cookies,
+    ClientIp(ip): ClientIp,
+    Path(name): Path<String>,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    let channel = channels::by_name(&user.username, &name, &db)
+        .await
+        .ok_or(OMError::ItemNotFound("Channel"))?;
+
+    sqlx::query!("DELETE FROM channels WHERE id = ?", channel.id)
+        .execute(&db)
+        .await?;
+
+    let diff = Some(json!({ "channel": channel.name }));
+    let name = Some(user.username.as_str());
+    audit::log(&db, name, name, AuditAction::ChannelDeleted, diff, ip).await;
+
+    Ok(())
 }
@@ -0,0 +1,438 @@
+//! Scheduled streams, which users plan ahead, and their export as iCalendar feeds.
+//!
+//! When a user goes live near the planned start of a scheduled stream, the entry is linked to the broadcast.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{Duration, NaiveDateTime, Utc};
+use tower_cookies::Cookies;
+
+use crate::{
+    errors::OMError,
+    objects::{OMConfig, ScheduleCreate, ScheduleQuery, ScheduledStream, User},
+    Db,
+};
+
+/// The maximum length of a title in characters.
+const MAX_TITLE_LEN: usize = 100;
+/// The maximum length of a description in characters.
+const MAX_DESCRIPTION_LEN: usize = 1000;
+/// How long a stream without a planned end is assumed to last, in minutes.
+const DEFAULT_DURATION: i64 = 60;
+/// How far from the planned start going live still counts as the scheduled stream, in minutes.
+const LINK_WINDOW: i64 = 30;
+/// How far back the calendar feeds reach, in days.
+const FEED_HISTORY: i64 = 30;
+/// The most entries returned at once.
+const MAX_ENTRIES: i64 = 500;
+
+/// Check the payload of a scheduled stream, returning the trimmed title and description.
+fn validate(body: &ScheduleCreate) -> Result<(&str, Option<&str>), OMError> {
+    let title = body.title.trim();
+    let description = body.description.as_deref().map(str::trim);
+    let too_long = description.is_some_and(|d| d.chars().count() > MAX_DESCRIPTION_LEN);
+    if title.is_empty()
+        || title.chars().count() > MAX_TITLE_LEN
+        || too_long
+        || body.ends_at.is_some_and(|e| e <= body.starts_at)
+    {
+        return Err(OMError::InvalidSchedule);
+    }
+    Ok((title, description.filter(|d| !d.is_empty())))
+}
+
+/// Link a new broadcast to the scheduled stream of the user that starts closest to now, within [`LINK_WINDOW`].
+pub async fn link(db: &Db, username: &str, broadcast_id: i64) -> Result<(), OMError> {
+    let now = Utc::now().naive_utc();
+    let (from, to) = (
+        now - Duration::minutes(LINK_WINDOW),
+        now + Duration::minutes(LINK_WINDOW),
+    );
+    sqlx::query!(
+        "
+        UPDATE schedules SET broadcast_id = ?1 WHERE id = (
+            SELECT id FROM schedules
+            WHERE user_id = ?2 AND broadcast_id IS NULL AND starts_at >= ?3 AND starts_at <= ?4
+            ORDER BY ABS(julianday(starts_at) - julianday(?5))
+            LIMIT 1
+        )
+        ",
+        broadcast_id,
+        username,
+        from,
+        to,
+        now
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Get the scheduled streams that haven't ended before `since`, soonest first.
+///
+/// Streams of private users are only included if `logged_in` is set.
+/// If there are more than [`MAX_ENTRIES`], the ones that start closest to now are kept.
+async fn entries(
+    db: &Db,
+    username: Option<&str>,
+    logged_in: bool,
+    since: NaiveDateTime,
+) -> Result<Vec<ScheduledStream>, OMError> {
+    // Streams without a planned end are over after the default duration
+    let started_since = since - Duration::minutes(DEFAULT_DURATION);
+    let now = Utc::now().naive_utc();
+    let mut entries = sqlx::query_as!(
+        ScheduledStream,
+        "
+        SELECT schedules.* FROM schedules
+        JOIN users ON users.username = schedules.user_id
+        WHERE (users.private = 0 OR ?1)
+        AND (?2 IS NULL OR schedules.user_id = ?2 COLLATE NOCASE)
+        AND (schedules.ends_at >= ?3 OR (schedules.ends_at IS NULL AND schedules.starts_at >= ?4))
+        ORDER BY ABS(julianday(schedules.starts_at) - julianday(?5))
+        LIMIT ?6
+        ",
+        logged_in,
+        username,
+        since,
+        started_since,
+        now,
+        MAX_ENTRIES
+    )
+    .fetch_all(db)
+    .await?;
+    entries.sort_by_key(|e| e.starts_at);
+    Ok(entries)
+}
+
+/// Find a scheduled stream of a user.
+async fn by_id(db: &Db, username: &str, id: i64) -> Result<ScheduledStream, OMError> {
+    sqlx::query_as!(
+        ScheduledStream,
+        "SELECT * FROM schedules WHERE id = ? AND user_id = ?",
+        id,
+        username
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or(OMError::ItemNotFound("Scheduled stream"))
+}
+
+/// List all scheduled streams of the currently logged in user, including past ones, newest first.
+pub async fn list_schedule(
+    State(db): State<Db>,
+    cookies: Cookies,
+) -> Result<Json<Vec<ScheduledStream>>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+
+    let entries = sqlx::query_as!(
+        ScheduledStream,
+        "SELECT * FROM schedules WHERE user_id = ? ORDER BY starts_at DESC",
+        user.username
+    )
+    .fetch_all(&db)
+    .await?;
+
+    Ok(Json(entries))
+}
+
+/// Schedule a stream for the currently logged in user.
+pub async fn create_schedule(
+    State(db): State<Db>,
+    cookies: Cookies,
+    Json(body): Json<ScheduleCreate>,
+) -> Result<Json<ScheduledStream>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    let (title, description) = validate(&body)?;
+
+    let entry = sqlx::query_as!(
+        ScheduledStream,
+        "
+        INSERT INTO schedules (user_id, title, description, starts_at, ends_at)
+        VALUES (?, ?, ?, ?, ?)
+        RETURNING *
+        ",
+        user.username,
+        title,
+        description,
+        body.starts_at,
+        body.ends_at
+    )
+    .fetch_one(&db)
+    .await?;
+
+    Ok(Json(entry))
+}
+
+/// Change a scheduled stream of the currently logged in user.
+pub async fn update_schedule(
+    State(db): State<Db>,
+    cookies: Cookies,
+    Path(id): Path<i64>,
+    Json(body): Json<ScheduleCreate>,
+) -> Result<Json<ScheduledStream>, OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    let entry = by_id(&db, &user.username, id).await?;
+    let (title, description) = validate(&body)?;
+
+    let entry = sqlx::query_as!(
+        ScheduledStream,
+        "
+        UPDATE schedules SET title = ?, description = ?, starts_at = ?, ends_at = ?
+        WHERE id = ?
+        RETURNING *
+        ",
+        title,
+        description,
+        body.starts_at,
+        body.ends_at,
+        entry.id
+    )
+    .fetch_one(&db)
+    .await?;
+
+    Ok(Json(entry))
+}
+
+/// Delete a scheduled stream of the currently logged in user.
+pub async fn delete_schedule(
+    State(db): State<Db>,
+    cookies: Cookies,
+    Path(id): Path<i64>,
+) -> Result<(), OMError> {
+    let user = User::from_req(State(db.clone()), cookies).await?;
+    let entry = by_id(&db, &user.username, id).await?;
+
+    sqlx::query!("DELETE FROM schedules WHERE id = ?", entry.id)
+        .execute(&db)
+        .await?;
+
+    Ok(())
+}
+
+/// List the upcoming and currently running scheduled streams of all users, soonest first.
+///
+/// Can be filtered by user with the `username` query parameter.
+pub async fn upcoming(
+    State(db): State<Db>,
+    cookies: Cookies,
+    Query(query): Query<ScheduleQuery>,
+) -> Result<Json<Vec<ScheduledStream>>, OMError> {
+    let logged_in = User::from_req(State(db.clone()), cookies).await.is_ok();
+    let now = Utc::now().naive_utc();
+
+    Ok(Json(
+        entries(&db, query.username.as_deref(), logged_in, now).await?,
+    ))
+}
+
+/// Get the scheduled streams of all users as an iCalendar feed.
+pub async fn calendar(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    cookies: Cookies,
+) -> Result<impl IntoResponse, OMError> {
+    let logged_in = User::from_req(State(db.clone()), cookies).await.is_ok();
+    let since = Utc::now().naive_utc() - Duration::days(FEED_HISTORY);
+    let entries = entries(&db, None, logged_in, since).await?;
+
+    Ok(ics(&config, "OvenMitts", &entries, true))
+}
+
+/// Get the scheduled streams of a user as an iCalendar feed.
+pub async fn user_calendar(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    cookies: Cookies,
+    Path(username): Path<String>,
+) -> Result<impl IntoResponse, OMError> {
+    let logged_in = User::from_req(State(db.clone()), cookies).await.is_ok();
+    let user = User::from_name(&username, &db)
+        .await
+        .filter(|u| !u.private || logged_in)
+        .ok_or(OMError::NotFound(username))?;
+    let since = Utc::now().naive_utc() - Duration::days(FEED_HISTORY);
+    let entries = entries(&db, Some(&user.username), logged_in, since).await?;
+
+    Ok(ics(&config, &user.display_name, &entries, false))
+}
+
+/// Render scheduled streams as an iCalendar (RFC 5545) response.
+///
+/// If `with_user` is set, the username is prepended to the titles, for feeds that mix several users.
+fn ics(
+    config: &OMConfig,
+    name: &str,
+    entries: &[ScheduledStream],
+    with_user: bool,
+) -> impl IntoResponse {
+    let host = config.base_url.host_str().unwrap_or("ovenmitts");
+    let mut cal = String::new();
+    line(&mut cal, "BEGIN:VCALENDAR");
+    line(&mut cal, "VERSION:2.0");
+    line(&mut cal, "PRODID:-//OvenMitts//Schedule//EN");
+    line(&mut cal, "CALSCALE:GREGORIAN");
+    line(&mut cal, &format!("X-WR-CALNAME:{}", escape(name)));
+    for entry in entries {
+        let ends_at = entry
+            .ends_at
+            .unwrap_or(entry.starts_at + Duration::minutes(DEFAULT_DURATION));
+        let summary = if with_user {
+            format!("{}: {}", entry.user_id, entry.title)
+        } else {
+            entry.title.clone()
+        };
+        line(&mut cal, "BEGIN:VEVENT");
+        line(&mut cal, &format!("UID:schedule-{}@{host}", entry.id));
+        line(
+            &mut cal,
+            &format!("DTSTAMP:{}", timestamp(entry.created_at)),
+        );
+        line(&mut cal, &format!("DTSTART:{}", timestamp(entry.starts_at)));
+        line(&mut cal, &format!("DTEND:{}", timestamp(ends_at)));
+        line(&mut cal, &format!("SUMMARY:{}", escape(&summary)));
+        if let Some(description) = &entry.description {
+            line(&mut cal, &format!("DESCRIPTION:{}", escape(description)));
+        }
+        line(&mut cal, &format!("URL:{}", config.base_url));
+        line(&mut cal, "END:VEVENT");
+    }
+    line(&mut cal, "END:VCALENDAR");
+
+    (
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        cal,
+    )
+}
+
+/// Format a time in UTC as an iCalendar date-time.
+fn timestamp(time: NaiveDateTime) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape the special characters of an iCalendar text value.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace(['\r', '\n'], "\\n")
+}
+
+/// Append a content line, folded so that no line is longer than 75 bytes.
+fn line(cal: &mut String, content: &str) {
+    let mut len = 0;
+    for c in content.chars() {
+        if len + c.len_utf8() > 75 {
+            cal.push_str("\r\n ");
+            // The leading space of the continuation counts towards its length
+            len = 1;
+        }
+        len += c.len_utf8();
+        cal.push(c);
+    }
+    cal.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+
+    /// An in-memory database with the migrations applied and a user `alice` with a broadcast.
+    async fn db() -> Db {
+        let db = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&db).await.unwrap();
+        sqlx::query(
+            "INSERT INTO users (username, display_name, password, stream_key) VALUES ('alice', 'Alice', '', 'key')",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO broadcasts (id, stream, user_id) VALUES (1, 'alice', 'alice')")
+            .execute(&db)
+            .await
+            .unwrap();
+        db
+    }
+
+    /// Schedule a stream for alice that starts `offset` minutes from now.
+    async fn schedule(db: &Db, id: i64, offset: i64) {
+        let starts_at = Utc::now().naive_utc() + Duration::minutes(offset);
+        sqlx::query("INSERT INTO schedules (id, user_id, title, starts_at) VALUES (?, 'alice', 'Stream', ?)")
+            .bind(id)
+            .bind(starts_at)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    async fn linked(db: &Db) -> Vec<i64> {
+        sqlx::query_scalar("SELECT id FROM schedules WHERE broadcast_id = 1")
+            .fetch_all(db)
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(escape(r"a;b,c\d"), r"a\;b\,c\\d");
+        assert_eq!(escape("one\r\ntwo\rthree\nfour"), r"one\ntwo\nthree\nfour");
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let mut cal = String::new();
+        let content = format!("DESCRIPTION:{}", "äöü€🦀".repeat(20));
+        line(&mut cal, &content);
+
+        assert!(cal.ends_with("\r\n"));
+        let lines: Vec<&str> = cal.trim_end_matches("\r\n").split("\r\n").collect();
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|l| l.len() <= 75));
+        assert!(lines[1..].iter().all(|l| l.starts_with(' ')));
+        let unfolded: String = lines
+            .iter()
+            .enumerate()
+            .map(|(i, l)| if i == 0 { *l } else { &l[1..] })
+            .collect();
+        assert_eq!(unfolded, content);
+    }
+
+    #[test]
+    fn keeps_short_lines() {
+        let mut cal = String::new();
+        line(&mut cal, &"a".repeat(75));
+        assert_eq!(cal, format!("{}\r\n", "a".repeat(75)));
+    }
+
+    #[tokio::test]
+    async fn links_closest_entry() {
+        let db = db().await;
+        schedule(&db, 1, -20).await;
+        schedule(&db, 2, 5).await;
+        schedule(&db, 3, 15).await;
+
+        link(&db, "alice", 1).await.unwrap();
+        assert_eq!(linked(&db).await, [2]);
+    }
+
+    #[tokio::test]
+    async fn ignores_entries_outside_window() {
+        let db = db().await;
+        schedule(&db, 1, -LINK_WINDOW - 5).await;
+        schedule(&db, 2, LINK_WINDOW + 5).await;
+
+        link(&db, "alice", 1).await.unwrap();
+        assert!(linked(&db).await.is_empty());
+    }
+}
@@ -0,0 +1,86 @@
+//! Categories, tags and full text search over users and their streams.
+
+use crate::{errors::OMError, objects::User, Db, TAG_RE};
+
+/// How many tags a stream can have.
+const MAX_TAGS: usize = 10;
+/// The maximum length of a category.
+const MAX_CATEGORY_LEN: usize = 50;
+/// The maximum number of search results.
+const MAX_RESULTS: i64 = 50;
+
+/// Normalize and validate a category. An empty category removes it.
+pub fn category(category: &str) -> Result<Option<String>, OMError> {
+    let category = category.trim();
+    if category.chars().count() > MAX_CATEGORY_LEN {
+        return Err(OMError::InvalidCategory);
+    }
+    Ok((!category.is_empty()).then(|| category.to_string()))
+}
+
+/// Normalize and validate a list of tags, returning them in the form they are stored in. No tags removes them.
+///
+/// Tags are lowercased and deduplicated, and may only contain alphanumeric characters and dashes.
+pub fn tags(tags: &[String]) -> Result<Option<String>, OMError> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if !TAG_RE.is_match(&tag) {
+            return Err(OMError::InvalidTag(tag));
+        }
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    if normalized.len() > MAX_TAGS {
+        return Err(OMError::InvalidTag(format!(
+            "at most {MAX_TAGS} tags are allowed"
+        )));
+    }
+    Ok((!normalized.is_empty()).then(|| normalized.join(" ")))
+}
+
+/// Turn user input into a FTS5 query, that matches all words as prefixes.
+///
+/// Everything except letters and numbers is dropped, so the input can't use the FTS5 query syntax.
+///
+/// ```
+/// # use ovenmitts::search::fts_query;
+/// assert_eq!(fts_query("speed run!").as_deref(), Some("\"speed\"* \"run\"*"));
+/// assert_eq!(fts_query("\"OR*").as_deref(), Some("\"OR\"*"));
+/// assert_eq!(fts_query(" - "), None);
+/// ```
+#[must_use]
+pub fn fts_query(input: &str) -> Option<String> {
+    let words: Vec<String> = input
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| format!("\"{w}\"*"))
+        .collect();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// Find the users whose name, stream title, category or tags match the query, best matches first.
+///
+/// Private users are only included if `logged_in` is set.
+pub async fn search(query: &str, logged_in: bool, db: &Db) -> Result<Vec<User>, OMError> {
+    let Some(query) = fts_query(query) else {
+        return Ok(Vec::new());
+    };
+    let users = sqlx::query_as!(
+        User,
+        "
+        SELECT users.* FROM stream_search
+        JOIN users ON users.rowid = stream_search.rowid
+        WHERE stream_search MATCH ? AND (users.private = 0 OR ?)
+        ORDER BY rank
+        LIMIT ?
+        ",
+        query,
+        logged_in,
+        MAX_RESULTS
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(users)
+}
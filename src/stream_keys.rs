@@ -0,0 +1,39 @@
+//! Additional stream keys of a user, which can be restricted and revoked on their own.
+
+use crate::{
+    objects::{StreamKey, User},
+    Db,
+};
+
+/// Find the user a stream key belongs to, together with the additional key if it isn't the user's main key.
+pub async fn lookup(stream_key: &str, db: &Db) -> Option<(User, Option<StreamKey>)> {
+    let user = sqlx::query_as!(User, "SELECT * FROM users WHERE stream_key = ?", stream_key)
+        .fetch_optional(db)
+        .await
+        .ok()?;
+    if let Some(user) = user {
+        return Some((user, None));
+    }
+
+    let key = sqlx::query_as!(
+        StreamKey,
+        "SELECT * FROM stream_keys WHERE stream_key = ?",
+        stream_key
+    )
+    .fetch_optional(db)
+    .await
+    .ok()??;
+    let user = User::from_name(&key.user_id, db).await?;
+    Some((user, Some(key)))
+}
+
+/// Remember when an additional key was last used.
+pub async fn touch(key: &StreamKey, db: &Db) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE stream_keys SET last_used_at = datetime('now') WHERE id = ?",
+        key.id
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
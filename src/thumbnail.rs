@@ -0,0 +1,153 @@
+//! Preview images of live streams, fetched from the thumbnail publisher of `OvenMediaEngine`.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, PoisonError, RwLock},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use tower_cookies::Cookies;
+
+use crate::{
+    objects::{GrantQuery, OMConfig, User},
+    playback::check_access,
+    registry::{LiveStream, StreamRegistry},
+    Db,
+};
+
+/// How long a thumbnail is served from the cache before it's fetched again.
+const MAX_AGE: Duration = Duration::from_secs(10);
+
+/// Shown when there is no thumbnail for a stream.
+const PLACEHOLDER: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 16 9"><rect width="16" height="9" fill="#202020"/><path d="M6.5 2.5v4l3.5-2z" fill="#808080"/></svg>"##;
+
+#[derive(Debug, Clone)]
+struct Thumbnail {
+    data: Vec<u8>,
+    content_type: String,
+    fetched_at: Instant,
+}
+
+/// Shared cache of the latest thumbnail of every stream.
+#[derive(Debug, Clone, Default)]
+pub struct ThumbnailCache(Arc<RwLock<HashMap<String, Thumbnail>>>);
+
+impl ThumbnailCache {
+    fn get(&self, name: &str) -> Option<Thumbnail> {
+        let cache = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        cache
+            .get(name)
+            .filter(|t| t.fetched_at.elapsed() < MAX_AGE)
+            .cloned()
+    }
+
+    fn insert(&self, name: &str, thumbnail: Thumbnail) {
+        let mut cache = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        // Drop the thumbnails of streams that went offline in the meantime
+        cache.retain(|_, t| t.fetched_at.elapsed() < MAX_AGE);
+        cache.insert(name.to_string(), thumbnail);
+    }
+}
+
+/// Fetch the latest thumbnail of a live stream from `OvenMediaEngine`.
+async fn fetch(config: &OMConfig, stream: &LiveStream) -> Option<Thumbnail> {
+    let url = config
+        .thumbnail_url
+        .as_ref()?
+        .join(&format!("{}/{}/thumb.jpg", stream.app, stream.name))
+        .ok()?;
+    let res = reqwest::Client::new()
+        .get(url)
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?;
+    let content_type = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("image/jpeg")
+        .to_string();
+    let data = res.bytes().await.ok()?.to_vec();
+
+    Some(Thumbnail {
+        data,
+        content_type,
+        fetched_at: Instant::now(),
+    })
+}
+
+/// Return the latest thumbnail of a live stream, or a placeholder if there is none.
+///
+/// Thumbnails of private streams are only returned to logged in users,
+/// and those of password protected streams only with a playback grant.
+pub async fn thumbnail(
+    State(db): State<Db>,
+    State(config): State<OMConfig>,
+    State(registry): State<StreamRegistry>,
+    State(cache): State<ThumbnailCache>,
+    cookies: Cookies,
+    Path(username): Path<String>,
+    Query(query): Query<GrantQuery>,
+) -> Response {
+    let viewer = User::from_req(State(db.clone()), cookies).await.ok();
+    let user = match User::from_name(&username, &db).await {
+        Some(u) if !u.private || viewer.is_some() => Some(u),
+        _ => None,
+    };
+    let user = match user {
+        Some(u) => check_access(Some(&u), viewer.as_ref(), query.grant.as_deref(), &db)
+            .await
+            .ok()
+            .map(|()| u),
+        None => None,
+    };
+    // Shared caches must not keep the thumbnails of private or password protected streams
+    let visibility = if user
+        .as_ref()
+        .is_some_and(|u| u.private || u.stream_password.is_some())
+    {
+        "private"
+    } else {
+        "public"
+    };
+
+    let thumbnail = match user.and_then(|u| registry.get(&u.username)) {
+        Some(stream) => match cache.get(&stream.name) {
+            Some(t) => Some(t),
+            None => fetch(&config, &stream)
+                .await
+                .inspect(|t| cache.insert(&stream.name, t.clone())),
+        },
+        None => None,
+    };
+
+    match thumbnail {
+        Some(t) => (
+            [
+                (header::CONTENT_TYPE, t.content_type),
+                (
+                    header::CACHE_CONTROL,
+                    format!("{visibility}, max-age={}", MAX_AGE.as_secs()),
+                ),
+            ],
+            t.data,
+        )
+            .into_response(),
+        None => (
+            [
+                (header::CONTENT_TYPE, "image/svg+xml".to_string()),
+                (header::CACHE_CONTROL, "no-cache".to_string()),
+            ],
+            PLACEHOLDER,
+        )
+            .into_response(),
+    }
+}